@@ -1,14 +1,153 @@
-use std::{env, fs::File, error::Error, path::PathBuf, process, process::Command, process::Stdio, 
-    io, io::prelude::*, io::Write, io::BufReader, io::BufWriter, io::ErrorKind, collections::HashMap};
+use std::{env, fs, fs::File, error::Error, path::Path, path::PathBuf, process, process::Command, process::Stdio, 
+    io, io::prelude::*, io::IsTerminal, io::Write, io::BufReader, io::BufWriter, io::ErrorKind, collections::BTreeMap, collections::HashMap};
 use walkdir::{DirEntry, WalkDir};
 use regex::Regex;
+use globset::Glob;
+use similar::TextDiff;
+use rayon::prelude::*;
+use ratatui::{crossterm::event, crossterm::event::Event, crossterm::event::KeyCode, crossterm::event::KeyEventKind, crossterm::event::KeyModifiers,
+    layout::Constraint, layout::Layout, style::Style, style::Stylize, widgets::Block, widgets::List, widgets::ListState, widgets::Paragraph};
 use flate2::read::GzDecoder;
+use bzip2::read::BzDecoder;
+use xz2::read::XzDecoder;
+use chardetng::EncodingDetector;
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
 use toml::Value;
-use log::error;
+use log::{debug, error};
 
 // A custom Result type that accepts generic types and uses Error trait to dynamically handle errors.
 type BoxResult<T> = Result<T, Box<dyn Error>>;
 
+// The number of pages above which the user is asked before the index is built on first run.
+const LARGE_INDEX_PAGES: usize = 5000;
+
+// The longest description stored in the index, with longer ones truncated.
+const MAX_DESCRIPTION_LENGTH: usize = 200;
+
+// The most line width variants of a page kept in the rendered page cache, evicting the least recently used.
+const MAX_WIDTH_VARIANTS: usize = 3;
+
+// The lowest fuzzy match score for a page name to be opened in fuzzy mode, unless set by the fuzzy_threshold config key.
+const DEFAULT_FUZZY_THRESHOLD: i64 = 50;
+
+// Fuzzy matches scoring within this much of the best match make the choice ambiguous, so the user is asked which to open.
+const FUZZY_AMBIGUITY_MARGIN: i64 = 5;
+
+// The directory of manual pages used when no config file sets one.
+const DEFAULT_MAN_PATH: &str = "/usr/share/man/";
+
+// The most .so redirects followed from one page, so redirect loops end.
+const MAX_SO_REDIRECTS: usize = 8;
+
+// Page files larger than this many bytes are never treated as .so redirect stubs, so full pages aren't read twice.
+const MAX_SO_STUB_SIZE: u64 = 1024;
+
+// The first bytes of gzip, bzip2 and xz compressed files.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const BZIP2_MAGIC: [u8; 3] = *b"BZh";
+const XZ_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+// The header written at the start of the index file, followed by a format version increased whenever the Cache struct
+// or the details extracted from pages change, so an index saved by an older build is rebuilt instead of being misread or reused.
+// The version is followed by the time the index was built, in seconds since the Unix epoch as 8 little endian bytes.
+const INDEX_MAGIC: [u8; 4] = *b"MANR";
+const INDEX_VERSION: u8 = 4;
+
+// Compression extensions of manual page files that can be read.
+const PAGE_EXTENSIONS: [&str; 3] = ["gz", "bz2", "xz"];
+
+// Conventional meanings of manual sections 1-9.
+const SECTION_DESCRIPTIONS: [&str; 9] = [
+    "Executable programs or shell commands",
+    "System calls (functions provided by the kernel)",
+    "Library functions, especially the C standard library",
+    "Special files (usually found in /dev)",
+    "File formats and conventions, e.g. /etc/passwd",
+    "Games",
+    "Miscellaneous (including macro packages and conventions), e.g. man(7), groff(7)",
+    "System administration commands (usually only for root)",
+    "Kernel routines [Non standard]",
+];
+
+// The short and long forms of the commands and options completed by the shell completion scripts, with their descriptions.
+const COMPLETION_FLAGS: [(&str, &str, &str); 45] = [
+    ("f", "whatis", "show the short descriptions of pages"),
+    ("k", "apropos", "search page names and descriptions"),
+    ("K", "global-apropos", "search the whole text of every page"),
+    ("l", "local-file", "format and display a local page file"),
+    ("w", "where", "print the file paths of a page"),
+    ("a", "all", "open every section of a page in turn"),
+    ("", "list", "list the indexed pages"),
+    ("", "glob", "open the pages matching a glob pattern"),
+    ("", "compare", "compare the rendered output of two page files"),
+    ("", "browse", "interactively filter and open indexed pages"),
+    ("", "export-all", "render the indexed pages into files"),
+    ("", "toc", "print the section headings of a page"),
+    ("", "json-page", "print the details and text of a page as JSON"),
+    ("", "stats", "print statistics about the troff source of a page"),
+    ("", "stats-index", "print statistics about the index"),
+    ("", "section-info", "print what a section number means"),
+    ("", "changed-since", "list pages changed since the last index or a duration"),
+    ("", "update-if-stale", "update the index if the manual directories changed"),
+    ("", "troff-lint", "report indexed pages that produce groff warnings"),
+    ("", "help", "print the help"),
+    ("", "usage", "print a short usage message"),
+    ("", "pager", "view pages with another pager"),
+    ("t", "troff", "write pages to stdout as PostScript"),
+    ("", "html", "write pages as HTML"),
+    ("s", "sections", "search a list of sections in order"),
+    ("", "format", "the output format of pages"),
+    ("", "output-dir", "the directory to write exported pages into"),
+    ("", "names-only", "list only page names"),
+    ("", "breakdown", "count search results in each section"),
+    ("", "regex", "match apropos search terms as regular expressions"),
+    ("", "json", "print whatis and apropos results as JSON"),
+    ("", "max-results", "stop a global apropos search after N pages"),
+    ("", "encoding-detect", "convert pages in legacy encodings"),
+    ("", "hyperlinks", "make links in pages clickable"),
+    ("", "color", "colour bold and underlined text in pages"),
+    ("", "fuzzy", "open the closest matching page name"),
+    ("", "no-index", "find pages without the index"),
+    ("", "fallback-section", "open the lowest section of a page missing from the section given"),
+    ("", "number", "show line numbers in the pager"),
+    ("", "show-command", "print the groff and pager commands"),
+    ("", "no-body", "leave the page text out of JSON page output"),
+    ("", "chroot", "read pages from a mounted root filesystem"),
+    ("", "explain", "print the equivalent man command"),
+    ("v", "verbose", "show which extra directory search results came from"),
+    ("q", "quiet", "build the index on first run without asking"),
+];
+
+// The narrowest and widest line widths pages are formatted at, so very narrow or wide terminals still give readable pages.
+const MIN_FORMAT_WIDTH: usize = 20;
+const MAX_FORMAT_WIDTH: usize = 250;
+
+// The SGR foreground colours of bold and underlined text with the colour option.
+const COLOR_BOLD: &str = "31";
+const COLOR_UNDERLINE: &str = "32";
+
+// A root directory set by the chroot option that all configured manual directories are rebased onto.
+static CHROOT: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Whether a requested page couldn't be found or opened, and whether any page was shown, for the exit status.
+static PAGE_FAILED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+static PAGE_SHOWN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Whether a page has been written to the output file yet, so later pages from the same run, such as every section with the all option, follow it.
+static OUTPUT_FILE_STARTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// An error that has already been reported to the user, so it only sets the exit status rather than being printed again.
+#[derive(Debug)]
+pub struct ReportedError;
+
+impl std::fmt::Display for ReportedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "error already reported")
+    }
+}
+
+impl Error for ReportedError {}
+
 // Set whether a function fails on errors or simply logs them.
 #[derive(PartialEq)]
 pub enum ErrorAction {
@@ -18,18 +157,64 @@ pub enum ErrorAction {
 
 // Get and parse user arguments and take appropriate actions.
 pub fn get_args() -> BoxResult<()> {
+    // Collect user arguments, merging in any default flags from the config file before parsing out modifier flags.
+    let cli_args: Vec<String> = env::args().collect();
+    let config = merged_config()?;
+    let (mut options, args) = parse_options(merge_default_args(config, cli_args))?;
+
+    // Rebase every manual directory onto the chroot directory before any are used.
+    if let Some(chroot) = options.chroot.as_ref() {
+        let chroot = fs::canonicalize(chroot).map_err(|e| format!("manr: cannot use chroot directory {}: {}", chroot, e))?;
+        CHROOT.set(chroot.to_string_lossy().to_string()).ok();
+    }
+
+    // Print the usage information before the index is checked, so it works on a system without one.
+    if args.len() == 2 && (args[1] == "--help" || args[1] == "--usage") {
+        match args[1].as_str() {
+            "--help" => print_help(),
+            _ => print_usage(),
+        }
+        return Ok(());
+    }
+
+    // Print a shell completion script, or the page names the scripts complete, without building an index as completion runs unattended.
+    if args.len() == 3 && args[1] == "--completions" {
+        print!("{}", completion_script(&args[2])?);
+        return Ok(());
+    }
+    if args.len() == 2 && args[1] == "--completion-pages" {
+        completion_pages();
+        return Ok(());
+    }
+
     // Set default values.
     let default_path = default_file_path()?.to_string();
-    let source_dir = env::current_dir()?;
-    let index_bin_path = PathBuf::from(&source_dir).join("index.bin");
-    
-    // Check if a bin file exists for the index cache. If not then create one.
-    if !index_bin_path.exists() {
-        index_cache()?;
+    let index_bin_path = PathBuf::from(index_file());
+
+    // Check if a bin file exists for the index cache. If not then create one, explaining the pause first to an interactive user.
+    // Pages are looked up in the manual directories directly if it can't be built, such as when the cache directory is read-only.
+    if !options.no_index && !index_bin_path.exists() {
+        if !options.quiet && io::stdin().is_terminal() && io::stdout().is_terminal() {
+            eprintln!("No index found; building manual page index (this may take a moment)...");
+            if !confirm_first_index()? {
+                eprintln!("Index not built. Run 'manr makewhatis' to build it later.");
+                return Ok(());
+            }
+        }
+        if let Err(e) = index_cache(None, false).and_then(|result| Ok(result?)) {
+            eprintln!("manr: warning: couldn't build the index ({}), searching the manual directories directly", e);
+            options.no_index = true;
+        }
+    // Otherwise check the index hasn't gone stale, except for the commands that update it or compare pages against it.
+    } else if !options.no_index && !args.get(1).is_some_and(|arg| ["makewhatis", "--update-if-stale", "--changed-since"].contains(&arg.as_str())) {
+        update_stale_index(&options)?;
+    }
+
+    // Print the equivalent man command instead of running anything.
+    if options.explain {
+        println!("{}", explain_command(&options, &args)?);
+        return Ok(());
     }
-    
-    // Collect user arguments.
-    let args: Vec<String> = env::args().collect();
     
     // Match user arguments according to the number supplied and subsequent details.
     match args.len() {
@@ -41,9 +226,10 @@ pub fn get_args() -> BoxResult<()> {
         // Or else check if a section number or flag/option and if valid ask for additional argument.
         2 => {
             // Check if a section number between 1-9 and if so ask for a related manual page.
-            if let Ok(section) = args[1].clone().parse::<u8>() {
+            if let Ok(section) = normalize_section(&args[1]).parse::<u8>() {
                 if (1..=9).contains(&section) {
                     println!("No manual entry for {}\n(Alternatively, what manual page do you want from section {}?)\nFor example, try 'manr man'.", section, section);
+                    page_failed();
                 }
             // Else check if command to update index cache or a valid flag/option and if the latter ask for related argument.
             } else if let Some(arg) = Some(args[1].clone()) {
@@ -51,7 +237,59 @@ pub fn get_args() -> BoxResult<()> {
                     // Command to update the index bin file containing all the manual page details. Runs automatically if empty.
                     // (Needs tweaked to check only for modified or added files since last run. Could also be auto run periodically using a cron job.)
                     "makewhatis" => {
-                        index_cache()?;
+                        index_cache(None, true)?;
+                    },
+                    // Command to update the index only if pages have been added or removed since it was built, such as from a cron job.
+                    "--update-if-stale" => {
+                        if index_is_stale()? {
+                            index_cache(None, true)?;
+                        } else {
+                            println!("The index is up to date.");
+                        }
+                    },
+                    // Command to render every indexed page, or those in the sections option, into files in the output directory.
+                    "--export-all" => {
+                        export_all(&options)?;
+                    },
+                    // Command to interactively filter the indexed pages and open them.
+                    "--browse" => {
+                        browse(&options)?;
+                    },
+                    // Command to report indexed pages that produce groff warnings or errors.
+                    "--troff-lint" => {
+                        troff_lint()?;
+                    },
+                    // Report statistics about the health of the index cache.
+                    "--stats-index" => {
+                        index_stats()?;
+                    },
+                    "--stats" => {
+                        println!("stats for what?");
+                    },
+                    "--section-info" => {
+                        println!("section info for what?");
+                    },
+                    "--toc" => {
+                        println!("table of contents for what?");
+                    },
+                    "--json-page" => {
+                        println!("json page for what?");
+                    },
+                    "--changed-since" => {
+                        println!("changed since when?");
+                    },
+                    "-l" | "--local-file" => {
+                        println!("local file what?");
+                    },
+                    "--glob" => {
+                        println!("glob what?");
+                    },
+                    "--compare" => {
+                        println!("compare what?");
+                    },
+                    // List every indexed page.
+                    "--list" => {
+                        list_pages(None, &options)?;
                     },
                     flag if flag.starts_with("-f") || flag == "--whatis" => {
                         println!("whatis what?");
@@ -59,17 +297,33 @@ pub fn get_args() -> BoxResult<()> {
                     flag if flag.starts_with("-k") || flag == "--apropos" => {
                         println!("apropos what?")
                     },
+                    "-K" | "--global-apropos" => {
+                        println!("search the text of pages for what?")
+                    },
                     // Check if argument begins with "--" or "-" and notify of unrecognised/invalid option. 
                     // Or else check if a valid manual page by running the lowest available section number.
                     _ => {
-                        if arg.starts_with("--") {
+                        if arg.starts_with('-') && delegate_to_system_man(env::args().skip(1).collect())? {
+                            return Ok(());
+                        } else if arg.starts_with("--") {
                             println!("manr: unrecognised option -- '{}'", arg);
                             help();
                         } else if arg.starts_with("-") {
                             println!("manr: invalid option -- '{}'", arg);
                             help();
                         } else {
-                            first_section(arg)?;
+                            // The index loaded to check the name is used to open the page as well. Without the index the files found
+                            // are only those of the whole name, so the manual directories are searched again for a page given with its section.
+                            let files = page_index(&arg, &options)?;
+                            match inline_section(&arg, &files)? {
+                                Some((page, section)) if options.no_index => {
+                                    run_section(&page.to_lowercase(), &section.to_lowercase(), &default_path, &options)?;
+                                },
+                                Some((page, section)) => {
+                                    run_section_in(&page.to_lowercase(), &section.to_lowercase(), &default_path, &files, &options)?;
+                                },
+                                None => first_section_in(arg, &files, &options)?,
+                            }
                         }
                     },
                 }
@@ -78,40 +332,89 @@ pub fn get_args() -> BoxResult<()> {
         // If one section number or a command and one argument is provided.
         3 => {
             // Check if a section number between 1-9 and if so run related file path.
-            if let Ok(section) = args[1].clone().parse::<u8>() {
+            if let Ok(section) = normalize_section(&args[1]).parse::<u8>() {
                 if (1..=9).contains(&section) {
                     let page = args[2].clone().to_lowercase();
-                    let file_path = format!("{}/man{}/{}.{}.gz", default_path, section, page, section);
-                    run(file_path)?;
+                    run_section(&page, &section.to_string(), &default_path, &options)?;
                 } else {
                     // Else run lowest section number available if valid manual name but provided section number is outside 1-9 range.
                     let page = args[2].clone().to_lowercase();
-                    first_section(page)?;
+                    first_section(page, &options)?;
                 }
             // Check if a flag/option is used and run the related function.
-            } else if let Some(arg) = Some(args[1].clone()) {
+            } else if let Some(arg) = Some(normalize_section(&args[1])) {
                 match arg.as_str() {
                     flag if flag.starts_with("-f") || flag == "--whatis" => {
                         let page = args[2].clone().to_lowercase();
-                        index_whatis_search(page)?;           
+                        index_whatis_search(page, &options)?;           
                     },
                     flag if flag.starts_with("-k") || flag == "--apropos" => {
+                        let search_term = args[2].clone();
+                        index_apropos_search(search_term, &options)?;           
+                    },
+                    // Search the whole text of every indexed page.
+                    "-K" | "--global-apropos" => {
                         let search_term = args[2].clone().to_lowercase();
-                        index_apropos_search(search_term)?;           
+                        global_apropos_search(search_term, &options)?;
+                    },
+                    // Open every page whose name matches a glob pattern one at a time.
+                    "--glob" => {
+                        let pattern = args[2].clone().to_lowercase();
+                        open_glob(pattern, &options)?;
+                    },
+                    "--compare" => {
+                        println!("compare {} with what?", args[2]);
+                    },
+                    // List the indexed pages in a section.
+                    "--list" => {
+                        let section = args[2].clone().to_lowercase();
+                        list_pages(Some(section), &options)?;
+                    },
+                    // Print statistics about a page's troff source instead of opening it.
+                    "--stats" => {
+                        let page = args[2].clone().to_lowercase();
+                        page_stats(page)?;
+                    },
+                    // Render a troff file, or the standard input when given "-", instead of an indexed page.
+                    "-l" | "--local-file" => {
+                        local_file(&args[2], &options)?;
+                    },
+                    // Update only the index entries for pages in a section, such as "3" for every library page.
+                    "makewhatis" => {
+                        let section = normalize_section(&args[2]).to_lowercase();
+                        index_cache(Some(&section), true)?;
+                    },
+                    // Print a page's details and rendered text as JSON for editors and other tools.
+                    "--json-page" => {
+                        let page = args[2].clone().to_lowercase();
+                        json_page(page, &options)?;
+                    },
+                    // List pages added or modified since the index was built ("last") or within a duration such as "7d".
+                    "--changed-since" => {
+                        changed_since(&args[2])?;
+                    },
+                    // Print the section headings of a page instead of opening it.
+                    "--toc" => {
+                        let page = args[2].clone().to_lowercase();
+                        page_toc(page)?;
+                    },
+                    // Print what a section number conventionally means.
+                    "--section-info" => {
+                        section_info(&args[2])?;
                     },
                     // Check if a section number, including those with an extended suffix including text, such as "1ssl".
                     sect if sect.chars().next().unwrap().is_digit(10) => {
                         let section = &arg;
-                        let sect_num = sect.chars().next().unwrap().to_string();
                         let page = args[2].clone().to_lowercase();
-                        let file_path = format!("{}/man{}/{}.{}.gz", default_path, sect_num, page, section);
-                        run(file_path)?;
+                        run_section(&page, section, &default_path, &options)?;
                     },
-                    // Check if additional arguments are valid manual page names and if so open sequentially.
-                    // (Needs a file queue to prompt user to continue, skip or quit between each file.)
+                    // Check if additional arguments are valid manual page names and if so open sequentially,
+                    // prompting to continue, skip or quit between each page.
                     // Or if begins with "--" or "-" notify of unrecognised/invalid option.
                     _ => {
-                        if arg.starts_with("--") {
+                        if arg.starts_with('-') && delegate_to_system_man(env::args().skip(1).collect())? {
+                            return Ok(());
+                        } else if arg.starts_with("--") {
                             println!("manr: unrecognised option -- '{}'", arg);
                             help();
                         } else if arg.starts_with("-") {
@@ -119,402 +422,3945 @@ pub fn get_args() -> BoxResult<()> {
                             help();
                         } else {
                             let page1 = arg.to_lowercase();
-                            let page2 = args[2].clone().to_lowercase();
-                            first_section(page1)?;
-                            first_section(page2)?;
+                            let page2 = normalize_section(&args[2]).to_lowercase();
+                            let mut queue = vec![QueuedPage::Page(page1)];
+                            // A trailing section number has no page following it to open.
+                            let trailing_section = if page2.starts_with(|c: char| c.is_ascii_digit()) {
+                                Some(page2)
+                            } else {
+                                queue.push(QueuedPage::Page(page2));
+                                None
+                            };
+                            if open_queue(queue, &default_path, &options)? {
+                                if let Some(section) = trailing_section {
+                                    println!("What manual page do you want from section {}?", section);
+                                }
+                            }
                         }
                     },
                 }
             }
         },
-        // For all the other cases check if a section or manual page is provided and load multiple files sequentially. 
-        // (Also needs to use file queue when implemented.)
+        // Compare the rendered output of two page files.
+        4 if args[1] == "--compare" => {
+            compare_pages(args[2].clone(), args[3].clone())?;
+        },
+        // For all the other cases check if a section or manual page is provided and load multiple files sequentially
+        // through the same queue, prompting between each page.
         _ => {
+            let mut queue: Vec<QueuedPage> = Vec::new();
+            let mut trailing_section = None;
             // Iterate over collected user arguments and skip the first default.
             let mut args_iter = args.iter().skip(1);
             // While arguments exist loop through them.
-            while let Some(arg) = args_iter.next().clone() {
+            while let Some(arg) = args_iter.next().map(|arg| normalize_section(arg)) {
                 match arg.as_str() {
                     // Check if a section number, optionally with an extended text suffix (such as "1ssl").
                     sect if sect.chars().next().unwrap().is_digit(10) => {
-                        let section = &arg.to_lowercase();
-                        let sect_num = sect.chars().next().unwrap().to_string().to_lowercase();
-                        let page = args_iter.next().clone().unwrap().to_string().to_lowercase();
-                        let file_path = format!("{}/man{}/{}.{}.gz", default_path, sect_num, page, section);
-                        run(file_path)?;
+                        let section = arg.to_lowercase();
+                        // A trailing section number has no page following it to open.
+                        match args_iter.next() {
+                            Some(page) => queue.push(QueuedPage::Section(page.to_lowercase(), section)),
+                            None => trailing_section = Some(section),
+                        }
                     }
                 _ => {
                     // Otherwise treat argument as a manual page name without a section specified.
-                    let page = arg.to_string().to_lowercase();
-                    first_section(page)?;
+                    queue.push(QueuedPage::Page(arg.to_string().to_lowercase()));
                     }
                 }
             }
+
+            if open_queue(queue, &default_path, &options)? {
+                if let Some(section) = trailing_section {
+                    println!("What manual page do you want from section {}?", section);
+                }
+            }
         }
     }
     
     Ok(())
 }
 
-// Get default directory for manual pages from config.toml.
-fn default_file_path() -> BoxResult<String> {
-    // Load the config file contents into a new String.
-    let mut config_toml = File::open("config.toml")?;
-    let mut config_str = String::new();
-    config_toml.read_to_string(&mut config_str)?;
-
-    // Parse the values from the config file.
-    let config_file: Value = toml::from_str(&config_str)?;
-    let default_path = config_file["default"]["file_path"].to_string();
-
-    Ok(default_path.trim_matches('"').to_string())
-}
+// Print every distinct indexed page name in alphabetical order, one per line, for the shell completion scripts to complete from.
+// Nothing is printed if the index can't be read, so completing a page name never builds it.
+fn completion_pages() {
+    let index = match read_index_file() {
+        Ok(index) => with_extra_index(index),
+        Err(e) => {
+            debug!("Couldn't read the index for completion: {}", e);
+            return;
+        },
+    };
 
-// Run and display manual files.
-pub fn run(path: String) -> BoxResult<()> {
-    // Extract gzip manual file and set action on errors to fail.
-    let contents = extract_gzip(path, ErrorAction::Fail)?.to_string();
-
-    // Load extracted gzip contents into groff application with UTF-8 formatting. (Seems to have issue formatting numbered/nested lists.)
-    let mut groff = Command::new("groff")
-    .arg("-mandoc")
-    .arg("-Tutf8")
-    .stdin(Stdio::piped())
-    .stdout(Stdio::piped())
-    .spawn()?;
+    let mut pages: Vec<String> = index.into_values().map(|cache| cache.page).collect();
+    pages.sort();
+    pages.dedup();
 
-    {
-        let stdin = groff.stdin.as_mut().unwrap();
-        stdin.write_all(contents.as_bytes())?;
+    for page in pages {
+        println!("{}", page);
     }
+}
 
-    groff.wait()?;
+// Build the completion script for a shell, which completes the flags and completes page names from the index.
+// Flags taking a file or directory complete paths instead.
+fn completion_script(shell: &str) -> BoxResult<String> {
+    let flags: Vec<String> = COMPLETION_FLAGS.iter()
+        .flat_map(|(short, long, _)| [format!("-{}", short), format!("--{}", long)])
+        .filter(|flag| flag != "-")
+        .chain(["makewhatis".to_string()])
+        .collect();
 
-    // Pass groff's formatted document into the less viewer application.
-    let mut less = Command::new("less")
-    .arg("-R")
-    .stdin(groff.stdout.unwrap())
-    .stdout(Stdio::inherit())
-    .spawn()?;
+    match shell {
+        "bash" => Ok(format!(r#"_manr() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
 
-    less.wait()?;
+    case "$prev" in
+        -l|--local-file|--compare|--chroot|--output-dir)
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+    esac
 
-    Ok(())
-}
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W "$(manr --completion-pages 2>/dev/null)" -- "$cur"))
+    fi
+}}
 
-// Open a file and read its contents into a Vector.
-fn open_file(path: String) -> BoxResult<Vec<u8>> {
-    let mut file = File::open(path.clone())?;
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents)?;
-    Ok(contents)
-}
+complete -F _manr manr
+"#, flags.join(" "))),
+        "zsh" => Ok(format!(r#"#compdef manr
 
-// Extract gzip files into String contents.
-pub fn extract_gzip(path: String, errors: ErrorAction) -> BoxResult<String> {
-    // Split file path from filename and format name by removing .gz extension and splitting at last "." character. 
-    let file_path = path.clone();
-    let mut filename = file_path.split("/").last().unwrap().trim_end_matches(".gz").rsplitn(2, '.');
-    let section = filename.next().unwrap();
-    let page = filename.next().unwrap();
+_manr() {{
+    case "${{words[CURRENT-1]}}" in
+        -l|--local-file|--compare|--chroot|--output-dir)
+            _files
+            return
+            ;;
+    esac
 
-    // Open the file path and read its contents into a new Vector. 
-    let file_result = open_file(path.clone());
-    let mut contents = Vec::new();
+    if [[ "$PREFIX" == -* ]]; then
+        compadd -- {}
+    else
+        compadd -- ${{(f)"$(manr --completion-pages 2>/dev/null)"}}
+    fi
+}}
 
-    // Match any errors to their kind and either print/exit or log/continue depending on setting of ErrorAction.
-    if errors == ErrorAction::Fail {
-        match file_result {
-            Ok(file) => {
-                contents = file;
-            },
-            Err(e) => {
-                // Downcast boxed error to type that implements the std Error trait.
-                if let Some(err) = e.downcast_ref::<io::Error>() {
-                    match err.kind() {
-                        ErrorKind::NotFound => println!("No manual entry for {} in section {}", &page, &section),
-                        ErrorKind::PermissionDenied => println!("Permission denied for {} in section {}", &page, &section),
-                        _ => println!("Error opening file {:?}", err),
-                    }
+if [[ "$funcstack[1]" == "_manr" ]]; then
+    _manr "$@"
+else
+    compdef _manr manr
+fi
+"#, flags.join(" "))),
+        "fish" => {
+            let mut script = String::from("complete -c manr -f -a '(manr --completion-pages 2>/dev/null)'\ncomplete -c manr -f -a makewhatis -d 'rebuild the index'\n");
+            for (short, long, description) in COMPLETION_FLAGS.iter() {
+                script.push_str(&format!("complete -c manr -l {}", long));
+                if !short.is_empty() {
+                    script.push_str(&format!(" -s {}", short));
                 }
-            process::exit(1);
-            }
-        };
-    } else {
-        match file_result {
-            Ok(file) => {
-                contents = file;
-            },
-            Err(e) => {
-                if let Some(err) = e.downcast_ref::<io::Error>() {
-                    match err.kind() {
-                        ErrorKind::NotFound => error!("No manual entry for {} in section {}", &page, &section),
-                        ErrorKind::PermissionDenied => error!("Permission denied for {} in section {}", &page, &section),
-                        _ => error!("Error opening file {:?}", err),
-                    }
+                if ["local-file", "compare", "chroot", "output-dir"].contains(long) {
+                    script.push_str(" -r -F");
                 }
+                script.push_str(&format!(" -d '{}'\n", description));
             }
-        };
+            Ok(script)
+        },
+        _ => Err(format!("manr: unsupported shell '{}', expected bash, zsh or fish", shell).into()),
+    }
+}
+
+// Ask whether to build the index on first run when the manual directories are large enough for it to take a while.
+// Only called when both stdin and stdout are terminals, otherwise the index is always built.
+fn confirm_first_index() -> BoxResult<bool> {
+    let pages: usize = man_roots()?.iter()
+        .map(|root| list_all_sections(root, index_settings().follow_symlinks).map(|files| files.len()).unwrap_or(0))
+        .sum();
+    if pages < LARGE_INDEX_PAGES {
+        return Ok(true);
     }
 
-    // Extract the contents of the opened file into a String.
-    let mut gzip = GzDecoder::new(&contents[..]);
-    let mut gzip_contents = String::new();
-    // Check if the file extracted successfully and if not log the error and continue.
-    match gzip.read_to_string(&mut gzip_contents) {
-        Ok(extracted) => Ok(extracted),
-        Err(e) =>
-            Err(error!("Error extracting gzip file for {} in section {}: {}", page, section, e)),
-    };
+    eprint!("Found {} manual pages to index. Build the index now? [Y/n]: ", pages);
+    io::stderr().flush()?;
 
-    Ok(gzip_contents)
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(!answer.trim().to_lowercase().starts_with('n'))
 }
 
-// Recursively list and sort all sections within a configured search directory.
-fn list_all_sections() -> BoxResult<Vec<DirEntry>> {
-    let default_path = default_file_path()?.to_string();
+// Format the man command line equivalent to the parsed options and arguments, such as "man -S 2:3 printf".
+// Options without a man equivalent are left out, and commands that only manr has are reported as errors.
+fn explain_command(options: &Options, args: &[String]) -> BoxResult<String> {
+    let quote = |arg: &str| if arg.is_empty() || arg.contains(char::is_whitespace) { format!("'{}'", arg) } else { arg.to_string() };
+    let mut command = vec!["man".to_string()];
 
-    // A regex for a suffix covering filenames formatted like "name.1.gz" or "name.1ssl.gz" with a numeric range of 1-9.
-    let suffix = Regex::new(r"\.([1-9])(?:[a-zA-Z]*)?\.gz$")?;
+    if let Some(pager) = options.pager.as_ref() {
+        command.push("-P".to_string());
+        command.push(quote(pager));
+    }
+    if !options.sections.is_empty() {
+        command.push("-S".to_string());
+        command.push(options.sections.join(":"));
+    }
+    if let Some(format) = options.format.as_ref() {
+        command.push(format!("-T{}", output_format(format)?.0));
+    }
+    if options.where_path {
+        command.push("-w".to_string());
+    }
+    if options.all {
+        command.push("-a".to_string());
+    }
+    if options.regex {
+        command.push("--regex".to_string());
+    }
+    if options.json {
+        return Err("manr: '--json' has no equivalent man command".into());
+    }
 
-    // List all files (including symbolic links) in a search directory adhering to the regex pattern.
-    let mut files: Vec<DirEntry> = WalkDir::new(default_path)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(|result| result.ok())
-        .filter(|result| result.file_type().is_file())
-        .filter(|result| suffix.is_match(result.file_name().to_string_lossy().as_ref()))
-        .collect();
-    
-    // Sort a page's sections in a ascending order according to the numeric range of the suffix.
-    files.sort_by_key(|entry| {
-        let sort_sections = suffix.captures(entry.file_name().to_string_lossy().as_ref()).unwrap()[1].parse::<u32>().unwrap();
-        sort_sections
-    });
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "makewhatis" | "--update-if-stale" => return Ok("mandb".to_string()),
+            "--whatis" => command.push("-f".to_string()),
+            "--apropos" => command.push("-k".to_string()),
+            "--global-apropos" => command.push("-K".to_string()),
+            "--local-file" => command.push("-l".to_string()),
+            "--browse" | "--export-all" | "--troff-lint" | "--stats" | "--stats-index" | "--glob" | "--compare" | "--list"
+                | "--section-info" | "--toc" | "--json-page" | "--changed-since" => {
+                return Err(format!("manr: '{}' has no equivalent man command", arg).into());
+            },
+            _ => command.push(quote(&normalize_section(arg))),
+        }
+    }
 
-    Ok(files)
+    Ok(command.join(" "))
 }
 
-// Format filename and short description for displaying in terminal (ie: name (1) - description text).
-fn format_filename_and_description(path: String) -> BoxResult<String> {
-    let description = get_description(path.clone())?.to_string();
-    let mut result = String::new();
-        
-    // Split path from filename and format filenames by removing .gz extension and splitting at last "." character. Then add relevant description.
-    if let Some(file) = Some(path.split("/").last().unwrap()) {
-        let mut filename = file.trim_end_matches(".gz").rsplitn(2, '.');
-        let section = filename.next().unwrap();
-        let page = filename.next().unwrap();
-
-        let new_filename = format!("{} ({}) - {}", page, section, description);
-        result.push_str(&new_filename);
+// Trim and normalise an argument that looks like a section number, so padded input such as " 1" or "01ssl" becomes "1" or "1ssl".
+// Other arguments are returned unchanged.
+fn normalize_section(arg: &str) -> String {
+    let trimmed = arg.trim();
+    if !trimmed.starts_with(|c: char| c.is_ascii_digit()) {
+        return arg.to_string();
     }
 
-    Ok(result)
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (number, suffix) = trimmed.split_at(digits);
+    let number = number.trim_start_matches('0');
+
+    format!("{}{}", if number.is_empty() { "0" } else { number }, suffix)
 }
 
-// Find and run/display the lowest section number if none is provided by user.
-fn first_section(page: String) -> BoxResult<()> {
-    // Load all entries in the index cache and create a new results Vector.
-    let files: HashMap<u32, Cache> = deserialise_index()?;
-    let mut results: Vec<String> = Vec::new();
+// User options set by modifier flags, which can appear anywhere in the arguments and apply to every page opened.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    // A pager command line to use instead of "less -R".
+    pub pager: Option<String>,
+    // Print only page names when listing pages.
+    pub names_only: bool,
+    // Sections to search, in order, for pages given without a section.
+    pub sections: Vec<String>,
+    // The output format for exported pages, such as text, html or ps.
+    pub format: Option<String>,
+    // The directory to write exported pages into.
+    pub output_dir: Option<String>,
+    // Print how many search results fall in each section after the results.
+    pub breakdown: bool,
+    // Guess the encoding of pages that aren't valid UTF-8 and convert them before formatting.
+    pub encoding_detect: bool,
+    // Make links in pages clickable in terminals that support hyperlinks.
+    pub hyperlinks: bool,
+    // Colour the bold and underlined text of pages shown in the terminal, such as headings.
+    pub color: bool,
+    // Open the closest matching page name when a page isn't found.
+    pub fuzzy: bool,
+    // Print the equivalent man command instead of running anything.
+    pub explain: bool,
+    // Build the index on first run without a message or confirmation.
+    pub quiet: bool,
+    // A mounted root filesystem to read pages from instead of this system's.
+    pub chroot: Option<String>,
+    // Leave the rendered text out of JSON page output.
+    pub no_body: bool,
+    // Show line numbers in the pager when it supports them.
+    pub number: bool,
+    // Show where search results outside the manual directories came from.
+    pub verbose: bool,
+    // Print the groff and pager commands a page would be opened with instead of running them.
+    pub show_command: bool,
+    // Open the lowest section of a page when it isn't in the section asked for.
+    pub fallback_section: bool,
+    // Print the file paths of pages instead of opening them.
+    pub where_path: bool,
+    // Open every section of a page in turn instead of only the lowest.
+    pub all: bool,
+    // Write pages straight to stdout instead of through the pager, even on a terminal.
+    pub no_pager: bool,
+    // Write pages formatted as HTML.
+    pub html: bool,
+    // A file to write formatted pages to instead of stdout, given after the page with the html option. It is replaced by the first page of a run.
+    pub output_file: Option<String>,
+    // Treat apropos search terms as regular expressions.
+    pub regex: bool,
+    // The most pages reported by a full text search.
+    pub max_results: Option<usize>,
+    // Print whatis and apropos results as a JSON array.
+    pub json: bool,
+    // Find pages by searching the manual directories instead of using the index.
+    pub no_index: bool,
+}
 
-    // Match page arg with page in the index cache and pass its file path to the Vector.
-    for (_, cache) in files.iter() {
-        if cache.page == page {
-            results.push(format!("{}", cache.file_path));
+// Separate modifier flags from the user arguments, returning the parsed options and the remaining arguments.
+fn parse_options(args: Vec<String>) -> BoxResult<(Options, Vec<String>)> {
+    let mut options = Options::default();
+    let mut remaining: Vec<String> = Vec::new();
+    let mut args_iter = args.into_iter();
+
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--pager" => {
+                options.pager = Some(args_iter.next().ok_or("manr: option '--pager' requires an argument")?);
+            },
+            flag if flag.starts_with("--pager=") => {
+                options.pager = Some(flag.trim_start_matches("--pager=").to_string());
+            },
+            "--names-only" => options.names_only = true,
+            "--breakdown" => options.breakdown = true,
+            "--encoding-detect" => options.encoding_detect = true,
+            "--hyperlinks" => options.hyperlinks = true,
+            "--color" | "--colour" => options.color = true,
+            "--fuzzy" => options.fuzzy = true,
+            "--explain" => options.explain = true,
+            "-q" | "--quiet" => options.quiet = true,
+            "--no-body" => options.no_body = true,
+            "--number" => options.number = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "--show-command" => options.show_command = true,
+            "--fallback-section" => options.fallback_section = true,
+            "-w" | "--where" => options.where_path = true,
+            "-a" | "--all" => options.all = true,
+            "-t" | "--troff" => options.format = Some("ps".to_string()),
+            "--html" => {
+                options.html = true;
+                options.format = Some("html".to_string());
+            },
+            "--no-pager" => options.no_pager = true,
+            "--regex" => options.regex = true,
+            "--json" => options.json = true,
+            "--no-index" => options.no_index = true,
+            "--max-results" => {
+                let max_results = args_iter.next().ok_or("manr: option '--max-results' requires an argument")?;
+                options.max_results = Some(max_results.parse().map_err(|_| format!("manr: invalid number of results '{}'", max_results))?);
+            },
+            "-s" | "--sections" => {
+                let sections = args_iter.next().ok_or(format!("manr: option '{}' requires an argument", arg))?;
+                options.sections = parse_section_list(&sections);
+            },
+            flag if flag.starts_with("--sections=") => {
+                options.sections = parse_section_list(flag.trim_start_matches("--sections="));
+            },
+            "--format" => {
+                options.format = Some(args_iter.next().ok_or("manr: option '--format' requires an argument")?.to_lowercase());
+            },
+            "--chroot" => {
+                options.chroot = Some(args_iter.next().ok_or("manr: option '--chroot' requires an argument")?);
+            },
+            "--output-dir" => {
+                options.output_dir = Some(args_iter.next().ok_or("manr: option '--output-dir' requires an argument")?);
+            },
+            _ => remaining.push(arg),
         }
     }
 
-    // Sort different section numbers in ascending order.
-    results.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
-
-    // Check at least one page section exists before trying to run the first file path.
-    if results.is_empty() {
-        println!("No manual entry for {}", page);
-    } else {
-        let first_file = results[0].to_string();
-        run(first_file)?;
+    // With the html option a trailing argument ending in .html is the file to write to, not a page (ie: manr --html 1 printf out.html).
+    if options.html && remaining.len() > 2 && remaining.last().is_some_and(|arg| arg.ends_with(".html")) {
+        options.output_file = remaining.pop();
     }
 
-    Ok(())
+    Ok((options, remaining))
 }
 
-// Search the contents and troff/markdown formatting of a file and get the description.
-fn get_description(path: String) -> BoxResult<String> {
-    let mut description = String::new();
-    let contents = extract_gzip(path, ErrorAction::Log)?.to_string();
-    let mut lines: Vec<&str> = Vec::new();
-
-    // Push each line of a file's contents into a Vector.
-    for line in contents.lines() {
-        lines.push(line);
-    }
+// Split a colon or comma separated list of sections, such as "2:3" or "2,3".
+fn parse_section_list(sections: &str) -> Vec<String> {
+    sections.split([',', ':'])
+        .map(|section| section.trim().to_lowercase())
+        .filter(|section| !section.is_empty())
+        .collect()
+}
 
-    // Turn the Vector into a iterator.
-    let mut iter = lines.iter();
-    // An additional check to break out of a loop after a pattern is found once.
-    let mut found: bool = false;
+// Insert the flags from the config file's [defaults] table after the program name, as if they were typed first.
+// Boolean keys add their flag when true and other values are passed as the flag's argument.
+// Flags given on the command line override the defaults, so those defaults are skipped.
+fn merge_default_args(config: &Value, cli_args: Vec<String>) -> Vec<String> {
+    let mut args: Vec<String> = cli_args.iter().take(1).cloned().collect();
 
-    // Iterate over the Vector's lines while they exist or until they match a pattern.
-    while let Some(line) = iter.next() {
-        // If line contains the relevant troff/markdown formatting then get the description from the next lines.
-        if line.to_lowercase().contains(".sh name") || line.to_lowercase().contains(".sh \"name\"") {
-            while let Some(next_lines) = iter.next() {
-                // Check if the next lines contain or end with additional formatting cointaining .nd.
-                if next_lines.to_lowercase().contains(".nd") {
-                    // If the trimmed line ends with additional formatting then skip it and get description from the following line.
-                    if next_lines.trim_end().ends_with(".nd") {
-                        if let Some(following_line) = iter.next() {
-                            let text = &following_line.to_lowercase();
-                            description.push_str(&text);
-                            found = true;
-                            break;
-                        }
-                    // Else if the next lines don't end with .nd remove the ".nd " formatting and get the description from that line.
-                    } else {
-                        let text = next_lines.to_lowercase().replacen(".nd ", "", 1).replacen(".nd", "", 1);
-                        description.push_str(&text);
-                        found = true;
-                        break;
-                    }
-                // Else check if the next lines contain "-" and after trimming, if ends with additional "-" or "- \\" formatting. 
-                // Then get description.
-                } else {
-                    if next_lines.contains("-") {
-                        if next_lines.trim_end().ends_with("-")  || next_lines.trim_end().ends_with("- \\") {
-                            if let Some(following_line) = iter.next() {
-                                let text = &following_line.to_lowercase();
-                                description.push_str(&text);
-                                found = true;    
-                                break; 
-                            }
-                        // Else if next lines don't end with "-" then split on that line if it has "- " formatting to get description.              
-                        } else {
-                            if let Some(text) = Some(&next_lines.to_lowercase().split("- ").last().unwrap().to_string()) {
-                                description.push_str(&text);
-                                found = true;
-                                break;
-                            }
+    if let Some(defaults) = config.get("defaults").and_then(|defaults| defaults.as_table()) {
+        for (key, value) in defaults.iter() {
+            let flag = format!("--{}", key.replace('_', "-"));
+            if cli_args.iter().skip(1).any(|arg| *arg == flag || arg.starts_with(&format!("{}=", flag))) {
+                continue;
+            }
 
-                        }
-                    }
-                }
+            match value {
+                Value::Boolean(true) => args.push(flag),
+                Value::Boolean(false) => {},
+                Value::String(text) => {
+                    args.push(flag);
+                    args.push(text.to_string());
+                },
+                Value::Integer(_) | Value::Float(_) => {
+                    args.push(flag);
+                    args.push(value.to_string());
+                },
+                _ => error!("Unsupported value for default flag {} in config file", key),
             }
         }
-        if found {
-            break;
-        }
     }
 
-    Ok(description)
+    args.extend(cli_args.into_iter().skip(1));
+    args
 }
 
-// An index cache struct for entry values to be stored in a related HashMap.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct Cache {
-    id: u32,
-    page: String,
-    section: String,
-    description: String,
-    file_path: String,
-}
+// Config files in the order they are merged, with the system defaults first and the user's config.toml overriding them.
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("/etc/manr/config.toml")];
+    paths.extend(user_config_path());
 
-// Create an index cache HashMap for faster searching of manual pages and short descriptions. Automatically runs if empty.
-// Can be updated on demand by using the makewhatis command or could be auto run periodically using a cron job.
-// (Needs modified to only update files changed or added since last run.)
-fn index_cache() -> BoxResult<std::io::Result<()>> {
-    let mut index = HashMap::new();
-    let all_files: Vec<DirEntry> = list_all_sections()?;
-    let mut results: Vec<String> = Vec::<String>::new();
-    // Initialise a counter for unique ids in the index HashMap.
-    let mut counter = 0;
-
-    // Populate a Vector with entries containing all index details concatenated.
-    for file in all_files {
-        let filename_with_desc = format_filename_and_description(file.clone().path().to_str().unwrap().to_owned())?.to_string();
-        let file_path = file.path().to_str().unwrap();
-        let result = filename_with_desc + " " + file_path;
-               
-        results.push(result);
-    }
-
-    for entry in results {
-        if !entry.is_empty() {
-            // Increase count by one for each new HashMap entry.
-            counter += 1;
-
-            // Populate index cache struct with split values.
-            let index_details = Cache {
-                id: counter,
-                page: entry.split_whitespace().nth(0).unwrap_or("#").to_owned(),
-                section: entry.split_whitespace().nth(1).map(|s| s.trim_matches(|c| c == '(' || c == ')')).unwrap_or("").to_owned(),
-                description: entry.split_once(" /").unwrap().0.split(" - ").last().unwrap_or("").to_owned(),
-                file_path: entry.split_whitespace().last().unwrap_or("").to_owned(),
-            };
+    paths
+}
 
-            // Insert index struct values into a HashMap.
-            index.insert(counter.clone(), index_details);
-        }
+// Find the user's config file, so manr works from any directory. The first that exists of these is used:
+// $MANR_CONFIG, $XDG_CONFIG_HOME/manr/config.toml, ~/.config/manr/config.toml and config.toml in the current directory.
+fn user_config_path() -> Option<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Some(path) = env::var_os("MANR_CONFIG").filter(|path| !path.is_empty()) {
+        candidates.push(PathBuf::from(path));
     }
+    if let Some(config_home) = env::var_os("XDG_CONFIG_HOME").filter(|path| !path.is_empty()) {
+        candidates.push(PathBuf::from(config_home).join("manr/config.toml"));
+    }
+    if let Some(home) = env::var_os("HOME").filter(|path| !path.is_empty()) {
+        candidates.push(PathBuf::from(home).join(".config/manr/config.toml"));
+    }
+    candidates.push(PathBuf::from("config.toml"));
 
-    // Serialise the index cache into a bin file.
-    let bin_file = File::create("index.bin")?;
-    let mut buffer = BufWriter::new(bin_file);
-    match bincode2::serialize_into(&mut buffer, &index) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
-    }?;
-
-    // Notify user that database was successfully updated.
-    println!("Successfully updated manual entries in database.");
-       
-    Ok(Ok(()))
+    candidates.into_iter().find(|path| path.exists())
 }
 
-// Deserialise the index bin file.
-fn deserialise_index() -> BoxResult<HashMap<u32, Cache>> {
-    let file = File::open("index.bin")?;
-    let buffer = BufReader::new(file);
-    let index: HashMap<u32, Cache> = bincode2::deserialize_from(buffer)?;
+// Get the merged config, or the error from reading it. The config files are only read once and kept for the rest of the run,
+// as settings are looked up on hot paths such as for every page indexed.
+fn merged_config() -> Result<&'static Value, String> {
+    static MERGED_CONFIG: std::sync::OnceLock<Result<Value, String>> = std::sync::OnceLock::new();
+
+    MERGED_CONFIG.get_or_init(|| read_merged_config().map_err(|e| e.to_string())).as_ref().map_err(|e| e.clone())
+}
+
+// Load and merge all existing config files over the built-in defaults, so each file only needs to set the keys it changes.
+// Without any config file the pages are read from the default manual directory.
+fn read_merged_config() -> BoxResult<Value> {
+    let mut merged: Value = toml::from_str(&format!("[default]\nfile_path = \"{}\"\n", DEFAULT_MAN_PATH))?;
+
+    for path in config_paths() {
+        if !path.exists() {
+            continue;
+        }
+
+        let config_file = read_config_file(&path)?;
+        merged = merge_config(merged, config_file);
+    }
+
+    Ok(merged)
+}
+
+// Load and parse a single config file.
+fn read_config_file(path: &Path) -> BoxResult<Value> {
+    // Load the config file contents into a new String.
+    let mut config_toml = File::open(path)?;
+    let mut config_str = String::new();
+    config_toml.read_to_string(&mut config_str)?;
+
+    // Parse the values from the config file.
+    let config_file: Value = toml::from_str(&config_str)?;
+
+    Ok(config_file)
+}
+
+// Recursively merge two config values. Tables are merged key by key and any other value in the override replaces the base.
+fn merge_config(base: Value, overrides: Value) -> Value {
+    match (base, overrides) {
+        (Value::Table(mut base), Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge_config(existing, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Table(base)
+        },
+        (_, overrides) => overrides,
+    }
+}
+
+// Read the user maintained descriptions.toml next to each config file, mapping "page.section" to a description that replaces the extracted one.
+// Later files override earlier ones in the same order as the config files, and unreadable files are logged and skipped.
+fn description_overrides() -> HashMap<String, String> {
+    let mut overrides = HashMap::new();
+
+    for config_path in config_paths() {
+        let path = config_path.with_file_name("descriptions.toml");
+        if !path.exists() {
+            continue;
+        }
+        match read_config_file(&path) {
+            Ok(Value::Table(table)) => {
+                for (key, value) in table {
+                    match value.as_str() {
+                        Some(description) => {
+                            overrides.insert(key.to_lowercase(), description.to_string());
+                        },
+                        None => error!("Unsupported description for {} in {}", key, path.display()),
+                    }
+                }
+            },
+            Ok(_) => {},
+            Err(e) => error!("Error reading {}: {}", path.display(), e),
+        }
+    }
+
+    overrides
+}
+
+// Get every manual directory to search, in order of precedence.
+// The default directories (or the $MANPATH directories) come first, followed by any directories from the man-db config file set by the mandb_conf config key
+// and any archives set by the archives config key.
+fn man_roots() -> BoxResult<Vec<String>> {
+    // Every directory in $MANPATH is searched when it is set, in place of the configured default directories.
+    let mut roots: Vec<String> = Vec::new();
+    for root in default_file_paths()? {
+        if !roots.iter().any(|existing| existing.trim_end_matches('/') == root.trim_end_matches('/')) {
+            roots.push(root);
+        }
+    }
+
+    let mandb_conf = config_setting("mandb_conf").and_then(|value| value.as_str().map(|s| s.to_string()));
+    if let Some(mandb_conf) = mandb_conf.filter(|mandb_conf| !mandb_conf.is_empty()) {
+        match fs::read_to_string(&mandb_conf) {
+            Ok(contents) => {
+                for root in mandb_conf_roots(&contents) {
+                    // Skip directories that are already searched, ignoring trailing slashes.
+                    if !roots.iter().any(|existing| existing.trim_end_matches('/') == root.trim_end_matches('/')) {
+                        roots.push(root);
+                    }
+                }
+            },
+            Err(e) => error!("Error reading {}: {}", mandb_conf, e),
+        }
+    }
+
+    for root in extra_doc_dirs().iter().cloned() {
+        if !roots.iter().any(|existing| existing.trim_end_matches('/') == root.trim_end_matches('/')) {
+            roots.push(root);
+        }
+    }
+
+    // Tar archives of pages set by the archives config key are searched last, as if they were directories.
+    let archives = config_setting("archives").and_then(|value| value.as_array().cloned()).unwrap_or_default();
+    for archive in archives.iter().filter_map(|archive| archive.as_str()) {
+        if is_archive(archive) {
+            roots.push(rebase_root(archive));
+        } else {
+            error!("Unsupported archive {} in config file, expected a .tar, .tar.gz or .tgz file", archive);
+        }
+    }
+
+    Ok(roots)
+}
+
+// Get the documentation directories set by the extra_doc_dirs config key, such as "/opt/foo/doc".
+// Any files in them named like manual pages are indexed, whether or not they are in manN subdirectories.
+// The directories are only read from the config file once, as search results are checked against them.
+fn extra_doc_dirs() -> &'static [String] {
+    static EXTRA_DOC_DIRS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+
+    EXTRA_DOC_DIRS.get_or_init(|| {
+        let dirs = config_setting("extra_doc_dirs").and_then(|value| value.as_array().cloned()).unwrap_or_default();
+
+        dirs.iter()
+            .filter_map(|dir| dir.as_str())
+            .map(rebase_root)
+            .filter(|dir| {
+                let exists = Path::new(dir).is_dir();
+                if !exists {
+                    error!("Documentation directory {} in config file not found", dir);
+                }
+                exists
+            })
+            .collect()
+    })
+}
+
+// Parse the manual directories from a man-db config file such as /etc/man_db.conf, in the order they are listed.
+// Directories come from MANDATORY_MANPATH lines, the manual directory of MANPATH_MAP lines and the first field of MANDB_MAP lines.
+// Directories that don't exist on this system are left out, as the config files usually list more than are installed.
+fn mandb_conf_roots(contents: &str) -> Vec<String> {
+    let mut roots: Vec<String> = Vec::new();
+
+    for line in contents.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let root = match fields.as_slice() {
+            ["MANDATORY_MANPATH", root, ..] => root,
+            ["MANPATH_MAP", _, root, ..] => root,
+            ["MANDB_MAP", root, ..] => root,
+            _ => continue,
+        };
+        let root = rebase_root(root);
+        if Path::new(&root).is_dir() && !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    roots
+}
+
+// Get the page file extensions to consider when indexing and looking up pages, in the order they are probed.
+// Set by the page_extensions config key, defaulting to every supported extension. Unsupported extensions are ignored.
+fn page_extensions() -> Vec<String> {
+    let configured: Option<Vec<String>> = config_setting("page_extensions")
+        .and_then(|value| value.as_array().cloned())
+        .map(|values| values.iter()
+            .filter_map(|value| value.as_str())
+            .map(|extension| extension.trim_start_matches('.').to_lowercase())
+            .filter(|extension| PAGE_EXTENSIONS.contains(&extension.as_str()))
+            .collect());
+
+    match configured {
+        Some(extensions) if !extensions.is_empty() => extensions,
+        _ => PAGE_EXTENSIONS.iter().map(|extension| extension.to_string()).collect(),
+    }
+}
+
+// Get an optional setting from the [default] table of config.toml.
+fn config_setting(key: &str) -> Option<Value> {
+    merged_config().ok()?.get("default")?.get(key).cloned()
+}
+
+// Get default directory for manual pages, the first of the default directories.
+fn default_file_path() -> BoxResult<String> {
+    Ok(default_file_paths()?.into_iter().next().unwrap_or_else(|| rebase_root(DEFAULT_MAN_PATH)))
+}
+
+// Get the default directories for manual pages in order of precedence, from $MANPATH or else from config.toml,
+// where file_path can be a single directory or an array of them (ie: ["/usr/share/man/", "/usr/local/share/man/"]).
+fn default_file_paths() -> BoxResult<Vec<String>> {
+    let config_file = merged_config()?;
+    let mut configured: Vec<String> = match config_file.get("default").and_then(|default| default.get("file_path")) {
+        Some(Value::Array(paths)) => paths.iter().filter_map(|path| path.as_str()).map(rebase_root).collect(),
+        Some(Value::String(path)) => vec![rebase_root(path)],
+        _ => Vec::new(),
+    };
+    if configured.is_empty() {
+        configured.push(rebase_root(DEFAULT_MAN_PATH));
+    }
+
+    let roots = manpath_roots(&configured);
+    if roots.is_empty() {
+        Ok(configured)
+    } else {
+        Ok(roots)
+    }
+}
+
+// Get the manual directories listed in $MANPATH (colon separated) in the order they are listed, as the man command does.
+// An empty entry, from a leading, trailing or doubled colon, is replaced by the configured default directories.
+fn manpath_roots(defaults: &[String]) -> Vec<String> {
+    let manpath = env::var("MANPATH").unwrap_or_default();
+    if manpath.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut roots: Vec<String> = Vec::new();
+    for root in manpath.split(':') {
+        if root.trim().is_empty() {
+            roots.extend(defaults.iter().cloned());
+        } else {
+            roots.push(rebase_root(root));
+        }
+    }
+
+    roots
+}
+
+// Prefix a configured manual directory with the chroot directory, if one is set (ie: /mnt/usr/share/man/ for /usr/share/man/).
+// The process isn't actually chrooted, only the paths are rebased.
+fn rebase_root(root: &str) -> String {
+    match CHROOT.get() {
+        Some(chroot) => Path::new(chroot).join(root.trim_start_matches('/')).to_string_lossy().to_string(),
+        None => root.to_string(),
+    }
+}
+
+// Get the index cache file path in the user's cache directory. A chroot directory gets its own index, named after a hash of the directory,
+// so inspecting another tree doesn't replace the index of this system's pages.
+fn index_file() -> String {
+    let file_name = match CHROOT.get() {
+        Some(chroot) => format!("index-{:016x}.bin", stable_hash(chroot.as_bytes())),
+        None => "index.bin".to_string(),
+    };
+
+    cache_dir().join(file_name).to_string_lossy().to_string()
+}
+
+// Hash bytes with 64-bit FNV-1a for naming files in the cache directory. Unlike the standard library's hasher
+// its output never changes between Rust releases, so files named after it are still found after a toolchain upgrade.
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Get the directory the index is kept in: $MANR_CACHE if set, else $XDG_CACHE_HOME/manr or ~/.cache/manr,
+// so the same index is used wherever manr is run from. Without a home directory the current directory is used.
+fn cache_dir() -> PathBuf {
+    if let Some(path) = env::var_os("MANR_CACHE").filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    if let Some(cache_home) = env::var_os("XDG_CACHE_HOME").filter(|path| !path.is_empty()) {
+        return PathBuf::from(cache_home).join("manr");
+    }
+    match env::var_os("HOME").filter(|path| !path.is_empty()) {
+        Some(home) => PathBuf::from(home).join(".cache/manr"),
+        None => PathBuf::from("."),
+    }
+}
+
+// Get the user's preferred languages in priority order from the colon separated $LANGUAGE list,
+// followed by the first locale set in $LC_ALL, $LC_MESSAGES or $LANG.
+// Each locale is expanded into less specific variants, such as "de_DE.UTF-8", "de_DE" and "de".
+fn preferred_languages() -> Vec<String> {
+    let mut locales: Vec<String> = Vec::new();
+
+    if let Ok(language) = env::var("LANGUAGE") {
+        locales.extend(language.split(':').filter(|locale| !locale.is_empty()).map(|locale| locale.to_string()));
+    }
+    for key in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(locale) = env::var(key) {
+            if !locale.is_empty() {
+                locales.push(locale);
+                break;
+            }
+        }
+    }
+
+    let mut languages: Vec<String> = Vec::new();
+    for locale in locales.iter() {
+        // The C and POSIX locales always use the untranslated pages.
+        if locale == "C" || locale == "POSIX" || locale.starts_with("C.") {
+            continue;
+        }
+
+        let without_modifier = locale.split('@').next().unwrap_or("");
+        let without_codeset = without_modifier.split('.').next().unwrap_or("");
+        let language = without_codeset.split('_').next().unwrap_or("");
+
+        for variant in [locale.as_str(), without_modifier, without_codeset, language] {
+            if !variant.is_empty() && !languages.iter().any(|existing| existing == variant) {
+                languages.push(variant.to_string());
+            }
+        }
+    }
+
+    languages
+}
+
+// Get the language directory a page is stored under relative to the manual root, or None if it is untranslated.
+fn page_language(file_path: &str, root: &str) -> Option<String> {
+    let relative = Path::new(file_path).strip_prefix(root).ok()?;
+    let mut components = relative.components();
+    let first = components.next()?.as_os_str().to_string_lossy().to_string();
+
+    // Untranslated pages are stored directly in section directories such as "man1".
+    if components.next().is_none() || first.to_lowercase().starts_with("man") {
+        None
+    } else {
+        Some(first)
+    }
+}
+
+// Find the manual directory containing a page file, returning its position in the directories (which are in order of precedence)
+// and the directory itself. Files outside all of them, such as pages in $MANR_EXTRA_PATH, are ranked last.
+fn page_root<'a>(file_path: &str, roots: &'a [String]) -> (usize, &'a str) {
+    roots.iter()
+        .position(|root| Path::new(file_path).starts_with(root) || file_path.starts_with(&format!("{}!/", root)))
+        .map_or((roots.len(), ""), |position| (position, roots[position].as_str()))
+}
+
+// Rank a page by the position of its language in the preferred languages, with untranslated pages ranked last.
+// Pages in languages the user hasn't asked for aren't ranked at all.
+fn language_rank(file_path: &str, root: &str, languages: &[String]) -> Option<usize> {
+    match page_language(file_path, root) {
+        Some(language) => languages.iter().position(|preferred| *preferred == language),
+        None => Some(languages.len()),
+    }
+}
+
+// Get the order to search sections in from the colon separated $MANSECT list (ie: "1:8:3") as with the man command,
+// or else the section_order config key (ie: ["1", "8", "3"]), lowercased.
+// Sections that aren't listed are searched after the listed ones in ascending order.
+fn section_order() -> Vec<String> {
+    let mansect = parse_section_list(&env::var("MANSECT").unwrap_or_default());
+    if !mansect.is_empty() {
+        return mansect;
+    }
+
+    let order = config_setting("section_order").and_then(|value| value.as_array().cloned()).unwrap_or_default();
+
+    order.iter()
+        .filter_map(|section| match section {
+            Value::String(section) => Some(section.trim().to_lowercase()),
+            Value::Integer(section) => Some(section.to_string()),
+            _ => None,
+        })
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+// Get the position of a section in the section order, or the end of the order if it isn't listed.
+// An extended section such as "3p" takes the position of its exact entry if it has one, or else of its base section "3".
+fn section_rank(section: &str, order: &[String]) -> usize {
+    let section = section.to_lowercase();
+
+    order.iter().position(|listed| *listed == section)
+        .or_else(|| order.iter().position(|listed| section.starts_with(listed.as_str())))
+        .unwrap_or(order.len())
+}
+
+// Build the file path for a page in a specific section, trying each preferred language directory in order
+// before falling back to the untranslated page.
+// Each configured page extension is probed in order, followed by the uncompressed page.
+fn localized_path(default_path: &str, sect_num: &str, page: &str, section: &str) -> String {
+    let extensions = page_extensions();
+
+    for language in preferred_languages() {
+        for extension in extensions.iter() {
+            let file_path = format!("{}/{}/man{}/{}.{}.{}", default_path, language, sect_num, page, section, extension);
+            if Path::new(&file_path).exists() {
+                return file_path;
+            }
+        }
+    }
+
+    for extension in extensions.iter() {
+        let file_path = format!("{}/man{}/{}.{}.{}", default_path, sect_num, page, section, extension);
+        if Path::new(&file_path).exists() {
+            return file_path;
+        }
+    }
+
+    // Uncompressed pages have no extension.
+    let file_path = format!("{}/man{}/{}.{}", default_path, sect_num, page, section);
+    if Path::new(&file_path).exists() {
+        return file_path;
+    }
+
+    format!("{}/man{}/{}.{}.{}", default_path, sect_num, page, section, extensions[0])
+}
+
+// Run and display a page in a specific section, handing over to the system man command if it doesn't exist and the config allows it.
+// With the fallback section option a page missing from the section is opened from its lowest section instead.
+fn run_section(page: &str, section: &str, default_path: &str, options: &Options) -> BoxResult<()> {
+    run_section_in(page, section, default_path, &page_index(page, options)?, options)
+}
+
+// Run and display a page in a specific section as with run_section, using an index that has already been loaded.
+fn run_section_in(page: &str, section: &str, default_path: &str, files: &HashMap<u32, Cache>, options: &Options) -> BoxResult<()> {
+    let file_path = section_path(page, section, default_path, files)?;
+    let missing = archive_member_path(&file_path).is_none() && !Path::new(&file_path).exists();
+
+    if missing && options.fallback_section {
+        if let Some(lowest) = matching_paths(page, &[], files)?.into_iter().next() {
+            eprintln!("manr: no manual entry for {} in section {}, showing section {}", page, section, page_and_section(&lowest).1);
+            return open_or_print_path(lowest, options);
+        }
+    }
+    let mut man_args = vec![section.to_string(), page.to_string()];
+    if options.where_path {
+        man_args.insert(0, "-w".to_string());
+    }
+    if missing && delegate_to_system_man(man_args)? {
+        return Ok(());
+    }
+    if missing && options.where_path {
+        println!("No manual entry for {} in section {}", page, section);
+        page_failed();
+        return Ok(());
+    }
+
+    open_or_print_path(file_path, options)
+}
+
+// Open a page, or just print its file path when the where option is set.
+fn open_or_print_path(file_path: String, options: &Options) -> BoxResult<()> {
+    if options.where_path {
+        println!("{}", file_path);
+        page_shown();
+        return Ok(());
+    }
+
+    run(file_path, options)
+}
+
+// Record that a requested page couldn't be found or opened, once the user has been told why.
+fn page_failed() {
+    PAGE_FAILED.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+// Record that a page was shown, or its path printed.
+fn page_shown() {
+    PAGE_SHOWN.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+// Get the exit status once every requested page has been handled. As with the man command it is 1 when pages were asked for
+// but none could be shown, and 0 when at least one was, even if others weren't found.
+pub fn exit_status() -> i32 {
+    if PAGE_FAILED.load(std::sync::atomic::Ordering::Relaxed) && !PAGE_SHOWN.load(std::sync::atomic::Ordering::Relaxed) {
+        1
+    } else {
+        0
+    }
+}
+
+// Run the system man command with the given arguments for cases manr can't handle, if fallback_to_system_man is set in the config.
+// Returns false without running anything if the fallback is disabled, or if it would run manr itself
+// (either because manr is installed as man or because this process was already started by the fallback).
+fn delegate_to_system_man(man_args: Vec<String>) -> BoxResult<bool> {
+    let enabled = config_setting("fallback_to_system_man").and_then(|value| value.as_bool()).unwrap_or(false);
+    if !enabled || env::var_os("MANR_DELEGATED").is_some() {
+        return Ok(false);
+    }
+
+    let system_man = match find_in_path("man") {
+        Some(system_man) => system_man,
+        None => return Ok(false),
+    };
+    let current_exe = env::current_exe().and_then(std::fs::canonicalize).ok();
+    if std::fs::canonicalize(&system_man).ok() == current_exe {
+        return Ok(false);
+    }
+
+    let status = Command::new(&system_man)
+    .args(&man_args)
+    .env("MANR_DELEGATED", "1")
+    .status()?;
+
+    if !status.success() {
+        return Err(format!("manr: {} exited with {}", system_man.display(), status).into());
+    }
+
+    Ok(true)
+}
+
+// Find an executable by name in the directories listed in $PATH.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+// Find the file path of a page in a specific section from the loaded index cache, preferring the user's languages.
+// Using the stored path keeps the casing of section directories such as "MAN1" or "man1x" intact.
+// When the index has no match the manual directories are searched, as pages that can't be indexed such as empty page files
+// are left out of it but should still be reported when opened. Falls back to building the standard path if neither has the page.
+fn section_path(page: &str, section: &str, default_path: &str, files: &HashMap<u32, Cache>) -> BoxResult<String> {
+    let roots = man_roots()?;
+    let languages = preferred_languages();
+
+    // Earlier manual directories win when several have the page in the section.
+    let best_path = |files: &HashMap<u32, Cache>| -> Option<String> {
+        let mut results: Vec<(usize, usize, String)> = Vec::new();
+        for (_, cache) in files.iter() {
+            if cache.page.to_lowercase() == page.to_lowercase() && cache.section.to_lowercase() == section.to_lowercase() {
+                let (root_rank, root) = page_root(&cache.file_path, &roots);
+                if let Some(rank) = language_rank(&cache.file_path, root, &languages) {
+                    results.push((rank, root_rank, cache.file_path.to_string()));
+                }
+            }
+        }
+
+        results.sort();
+        results.into_iter().next().map(|(_, _, file_path)| file_path)
+    };
+
+    if let Some(file_path) = best_path(files).or_else(|| filesystem_index(page).ok().and_then(|found| best_path(&found))) {
+        return Ok(file_path);
+    }
+
+    let sect_num = section.chars().next().unwrap_or('1').to_string();
+    Ok(localized_path(default_path, &sect_num, page, section))
+}
+
+// Run and display manual files.
+// The page is streamed from the decompressor through groff into the pager, at the terminal's line width.
+// Degrades gracefully when external tools are missing, in this order:
+// 1. Format with groff and display in the less pager.
+// 2. Format with groff and print straight to stdout if less is missing.
+// 3. Strip the troff macros from the page source and display it in less if groff is missing.
+// 4. Strip the troff macros and print straight to stdout if both groff and less are missing.
+// With a format option other than text, such as ps from the troff option, the page is formatted for that device instead.
+pub fn run(path: String, options: &Options) -> BoxResult<()> {
+    let device = match options.format.as_ref() {
+        Some(format) => output_format(format)?.0,
+        None => "utf8",
+    };
+
+    run_device(path, device, options)?;
+    page_shown();
+
+    Ok(())
+}
+
+// Run and display a manual file formatted for an output device. Terminal text ("utf8") is shown as described for run,
+// while other devices such as "ps" or "pdf" are streamed to stdout (or the output file option) as they are, without a pager.
+// Groff runs in a temporary directory for HTML, as it writes images for equations and tables into its working directory.
+fn run_device(path: String, device: &str, options: &Options) -> BoxResult<()> {
+    let path = follow_so_redirects(path)?;
+    let (page, section) = page_and_section(&path);
+    let terminal_text = device == "utf8";
+    let width = if terminal_text { format_width() } else { None };
+
+    // Show a cached rendering of the page as it is now at the same line width, before running groff.
+    // The cache is skipped when only showing the commands, as they are what would render the page.
+    let cache_path = if terminal_text { rendered_cache_path(&path, width, options) } else { None };
+    if let (Some(cache_path), false) = (cache_path.as_ref(), options.show_command) {
+        if let Some(cached) = cached_rendering(cache_path) {
+            return display_formatted(cached, options);
+        }
+    }
+
+    let (mut command, mut reader) = formatter_input(&path, device, width, options, ErrorAction::Fail)?;
+
+    if options.show_command {
+        print_commands(&command, options);
+        return Ok(());
+    }
+
+    // The page is streamed from the decompressor through groff into the pager, so memory use stays flat for large pages.
+    // Only the rendered page cache and colouring need the whole output, so it is collected first for those.
+    if terminal_text && cache_path.is_none() && !options.color {
+        pipe_formatter(command, reader, &path, |formatted| display_formatted(formatted, options))?;
+        return Ok(());
+    }
+    if terminal_text {
+        let (mut rendered, formatted) = format_text(command, reader, &path)?;
+        if options.color {
+            rendered = colorize(&rendered);
+        }
+        if let (Some(cache_path), true) = (cache_path, formatted) {
+            store_rendering(&cache_path, &rendered);
+        }
+        return display_formatted(io::Cursor::new(rendered), options);
+    }
+
+    let html_dir = if device == "html" {
+        let html_dir = env::temp_dir().join(format!("manr-html-{}", process::id()));
+        fs::create_dir_all(&html_dir)?;
+        command.current_dir(&html_dir);
+        Some(html_dir)
+    } else {
+        None
+    };
+
+    let mut groff = match command.spawn() {
+        Ok(groff) => groff,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(format!("manr: {}, it is required to render {}", formatter_not_found(), device).into());
+        },
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    // Feed the page into groff on a separate thread while its output is being read, so neither side can block on a full pipe.
+    let mut groff_stdin = groff.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || io::copy(&mut reader, &mut groff_stdin));
+
+    // Output for other devices may be binary, so it is copied to stdout as bytes.
+    let mut formatted = groff.stdout.take().unwrap();
+    let mut output: Box<dyn Write> = match options.output_file.as_ref() {
+        Some(output_file) => {
+            let file = if OUTPUT_FILE_STARTED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                File::options().append(true).open(output_file)?
+            } else {
+                File::create(output_file)?
+            };
+            Box::new(BufWriter::new(file))
+        },
+        None => Box::new(io::stdout()),
+    };
+    let copied = (|| -> io::Result<()> {
+        let mut buffer = [0; 8192];
+        loop {
+            let length = formatted.read(&mut buffer)?;
+            if length == 0 {
+                return output.flush();
+            }
+            output.write_all(&buffer[..length])?;
+        }
+    })();
+    // Ignore a broken pipe caused by a command reading the output exiting early.
+    if let Err(e) = copied {
+        if e.kind() != ErrorKind::BrokenPipe {
+            return Err(Box::new(e));
+        }
+    }
+
+    match writer.join() {
+        Ok(Ok(_)) => {},
+        // Groff stops reading when the command reading its output exits early.
+        Ok(Err(e)) if e.kind() == ErrorKind::BrokenPipe => {},
+        Ok(Err(e)) => error!("Error extracting gzip file for {} in section {}: {}", page, section, e),
+        Err(_) => error!("Error extracting gzip file for {} in section {}", page, section),
+    }
+    groff.wait()?;
+
+    // Only the HTML itself is kept, so any images groff wrote are removed with the directory.
+    if let Some(html_dir) = html_dir {
+        if let Err(e) = fs::remove_dir_all(&html_dir) {
+            error!("Error removing {}: {}", html_dir.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+// Extract a page and format it with groff as UTF-8 terminal text, returning the output as a String instead of displaying it, for use by external tools.
+// Any .so redirect is followed and the preprocessors the page asks for are run, at groff's default line width.
+pub fn render_page(path: &str) -> BoxResult<String> {
+    let path = follow_so_redirects(path.to_string())?;
+    let (command, reader) = formatter_input(&path, "utf8", None, &Options::default(), ErrorAction::Log)?;
+    let (formatted, _) = format_text(command, reader, &path)?;
+
+    Ok(String::from_utf8_lossy(&formatted).to_string())
+}
+
+// Open a page as a decompressed stream and build the formatter command for an output device, at a line width if one is given.
+// The first line is read ahead to find which preprocessors the page asks for, then put back in front of the rest of the stream.
+fn formatter_input(path: &str, device: &str, width: Option<usize>, options: &Options, errors: ErrorAction) -> BoxResult<(Command, impl Read + Send + 'static)> {
+    let (page, section) = page_and_section(path);
+    let mut source = page_reader(path.to_string(), errors)?;
+
+    // Legacy pages that aren't valid UTF-8 are converted when encoding detection is enabled, and groff is told to read UTF-8 input.
+    let mut input_encoding = None;
+    if options.encoding_detect {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+        let (contents, converted) = decode_page(&bytes, &page, &section);
+        if converted {
+            input_encoding = Some("-Kutf-8");
+        }
+        source = Box::new(io::Cursor::new(contents.into_bytes()));
+    }
+    let mut page_source = BufReader::new(source);
+
+    let mut first_line = Vec::new();
+    page_source.read_until(b'\n', &mut first_line)?;
+    let mut preprocessors = preprocessor_flags(&String::from_utf8_lossy(&first_line));
+    preprocessors.extend(input_encoding);
+    let reader = io::Cursor::new(first_line).chain(page_source);
+
+    let mut command = groff_command(device, &preprocessors);
+    let uses_groff = is_groff(&command.get_program().to_string_lossy());
+    if let Some(width) = width {
+        if uses_groff {
+            command.args([format!("-rLL={}n", width), format!("-rLT={}n", width)]);
+        } else {
+            command.args(["-O".to_string(), format!("width={}", width)]);
+        }
+    }
+    // Links from .UR and .MT macros become clickable OSC 8 hyperlinks if the terminal supports them, or are shown as text.
+    if options.hyperlinks && uses_groff && device == "utf8" {
+        command.arg(if supports_hyperlinks() { "-rU1" } else { "-rU0" });
+    }
+    // Colouring relies on grotty marking bold and underlined text with SGR escapes rather than overstriking.
+    if options.color && uses_groff && device == "utf8" {
+        command.env_remove("GROFF_NO_SGR");
+    }
+    // Silence warnings from pages with benign formatting issues when set in the config file.
+    if config_setting("suppress_groff_warnings").and_then(|value| value.as_bool()).unwrap_or(false) {
+        command.stderr(Stdio::null());
+    }
+
+    Ok((command, reader))
+}
+
+// Format a page stream as terminal text with a prepared formatter command, returning the output and whether the formatter succeeded.
+// If groff isn't installed the page source is returned with its macros stripped instead, after a warning.
+fn format_text<R: Read + Send + 'static>(command: Command, reader: R, path: &str) -> BoxResult<(Vec<u8>, bool)> {
+    pipe_formatter(command, reader, path, |mut formatted| {
+        let mut output = Vec::new();
+        formatted.read_to_end(&mut output)?;
+        Ok(output)
+    })
+}
+
+// Run a prepared formatter command over a page stream, passing its output to a handler as it is produced,
+// and return the handler's result and whether the formatter succeeded.
+// If groff isn't installed the handler is given the page source with its macros stripped instead, after a warning.
+fn pipe_formatter<R, T, F>(mut command: Command, mut reader: R, path: &str, handle_output: F) -> BoxResult<(T, bool)>
+where
+    R: Read + Send + 'static,
+    F: FnOnce(Box<dyn Read + Send>) -> BoxResult<T>,
+{
+    let (page, section) = page_and_section(path);
+
+    let mut groff = match command.spawn() {
+        Ok(groff) => groff,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // Stripping the macros needs the whole page source, which is fine as a last resort.
+            let mut contents = String::new();
+            if let Err(e) = reader.read_to_string(&mut contents) {
+                error!("Error extracting gzip file for {} in section {}: {}", page, section, e);
+            }
+            eprintln!("manr: warning: {}, displaying unformatted page source", formatter_not_found());
+            return Ok((handle_output(Box::new(io::Cursor::new(strip_troff(&contents).into_bytes())))?, false));
+        },
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    // Feed the page into groff on a separate thread while its output is being read, so neither side can block on a full pipe.
+    let mut groff_stdin = groff.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || io::copy(&mut reader, &mut groff_stdin));
+
+    let output = handle_output(Box::new(groff.stdout.take().unwrap()))?;
+
+    match writer.join() {
+        Ok(Ok(_)) => {},
+        // Groff stops reading if it exits before the end of the page, or when the pager is closed early.
+        Ok(Err(e)) if e.kind() == ErrorKind::BrokenPipe => {},
+        Ok(Err(e)) => error!("Error extracting gzip file for {} in section {}: {}", page, section, e),
+        Err(_) => error!("Error extracting gzip file for {} in section {}", page, section),
+    }
+    let status = groff.wait()?;
+
+    Ok((output, status.success()))
+}
+
+// Print the groff command a page would be formatted with, followed by the post_process command if one is set
+// and the pager command if a pager would be used, one per line in the order the page would pass through them.
+fn print_commands(groff: &Command, options: &Options) {
+    let groff_args: Vec<String> = groff.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+    println!("{}", command_line(&groff.get_program().to_string_lossy(), &groff_args));
+
+    if let Some(post_process) = config_setting("post_process").and_then(|value| value.as_str().map(|s| s.to_string())) {
+        if !post_process.trim().is_empty() {
+            println!("{}", post_process.trim());
+        }
+    }
+
+    if uses_pager(options) {
+        let (pager_name, pager_args) = pager_command(options);
+        println!("{}", command_line(&pager_name, &pager_args));
+    }
+}
+
+// Join a program and its arguments into a command line, quoting arguments that contain whitespace.
+fn command_line(program: &str, args: &[String]) -> String {
+    let mut words = vec![program.to_string()];
+    words.extend(args.iter().map(|arg| if arg.is_empty() || arg.contains(char::is_whitespace) { format!("'{}'", arg) } else { arg.to_string() }));
+
+    words.join(" ")
+}
+
+// Get the line width to format pages at, from $MANWIDTH or else the terminal width when stdout is a terminal,
+// kept within a readable range. Returns None to keep groff's default width.
+fn format_width() -> Option<usize> {
+    let manwidth = env::var("MANWIDTH").ok().and_then(|width| width.trim().parse::<usize>().ok()).filter(|width| *width > 0);
+
+    manwidth.or_else(|| if io::stdout().is_terminal() { terminal_width() } else { None })
+        .map(|width| width.clamp(MIN_FORMAT_WIDTH, MAX_FORMAT_WIDTH))
+}
+
+// Check whether the terminal is likely to support OSC 8 hyperlink escapes.
+// Output that isn't going to a terminal, and terminals known not to handle them, get plain link text instead.
+fn supports_hyperlinks() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+
+    io::stdout().is_terminal() && !term.is_empty() && term != "dumb" && term != "linux"
+}
+
+// Get the file in the rendered page cache for a page at a line width, when the cache_rendered_pages config key is set.
+// Files are named after the page and section, a hash of the page path and options that change the output, the page file's
+// modification time and the width, so every width variant of a page shares the same prefix (ie: name.1.0123456789abcdef.1700000000.w120)
+// and a page that changes on disk gets a new file rather than its old rendering.
+fn rendered_cache_path(path: &str, width: Option<usize>, options: &Options) -> Option<PathBuf> {
+    if !config_setting("cache_rendered_pages").and_then(|value| value.as_bool()).unwrap_or(false) {
+        return None;
+    }
+
+    let (page, section) = page_and_section(path);
+    let key = format!("{}\0{}\0{}\0{}", path, options.encoding_detect, options.hyperlinks, options.color);
+    let width = width.map_or("default".to_string(), |width| format!("w{}", width));
+
+    Some(cache_dir().join("render_cache").join(format!("{}.{}.{:016x}.{}.{}", page, section, stable_hash(key.as_bytes()), file_mtime(path), width)))
+}
+
+// Open a cached rendering, marking it as recently used. The page's modification time is part of the file name,
+// so an existing file is always a rendering of the current page.
+fn cached_rendering(cache_path: &Path) -> Option<File> {
+    let cached = File::options().append(true).read(true).open(cache_path).ok()?;
+
+    // The modification time records when each width variant was last used, for eviction.
+    if let Err(e) = cached.set_modified(std::time::SystemTime::now()) {
+        error!("Error updating {}: {}", cache_path.display(), e);
+    }
+
+    Some(cached)
+}
+
+// Save a rendering in the cache, then remove renderings of older versions of the page and evict the least recently used
+// width variants beyond the limit. Failures only affect caching, so they are logged rather than returned.
+fn store_rendering(cache_path: &Path, rendered: &[u8]) {
+    let cache_dir = cache_path.parent().unwrap_or(Path::new("."));
+    if let Err(e) = fs::create_dir_all(cache_dir).and_then(|_| fs::write(cache_path, rendered)) {
+        error!("Error writing {}: {}", cache_path.display(), e);
+        return;
+    }
+
+    // Renderings of the same page path and options share the file name up to the modification time,
+    // and width variants of the current version also share the modification time.
+    let file_name = cache_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let mut parts = file_name.rsplitn(3, '.');
+    let (mtime, prefix) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(mtime), Some(prefix)) => (mtime.to_string(), format!("{}.", prefix)),
+        _ => return,
+    };
+
+    let mut variants: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(cache_dir).into_iter().flatten().filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let entry_mtime = match name.strip_prefix(&prefix).and_then(|rest| rest.split_once('.')) {
+            Some((entry_mtime, _)) => entry_mtime.to_string(),
+            None => continue,
+        };
+
+        if entry_mtime != mtime {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                error!("Error removing {}: {}", entry.path().display(), e);
+            }
+        } else if let Some(modified) = entry.metadata().ok().and_then(|metadata| metadata.modified().ok()) {
+            variants.push((modified, entry.path()));
+        }
+    }
+
+    if variants.len() > MAX_WIDTH_VARIANTS {
+        variants.sort();
+        for (_, path) in variants.iter().take(variants.len() - MAX_WIDTH_VARIANTS) {
+            if let Err(e) = fs::remove_file(path) {
+                error!("Error removing {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// Display groff's output, first piping it through the post_process command from the config file if one is set.
+// If the command isn't installed a warning is printed and the output is displayed unprocessed.
+fn display_formatted<R: Read + Send + 'static>(mut formatted: R, options: &Options) -> BoxResult<()> {
+    let command_line = match config_setting("post_process").and_then(|value| value.as_str().map(|s| s.to_string())) {
+        Some(command_line) if !command_line.trim().is_empty() => command_line,
+        _ => return display_page(formatted, options),
+    };
+    let mut command_args = command_line.split_whitespace();
+    let command_name = command_args.next().unwrap_or("");
+
+    let processor = Command::new(command_name)
+    .args(command_args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn();
+
+    let mut processor = match processor {
+        Ok(processor) => processor,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("manr: warning: post_process command '{}' not found, displaying unprocessed output", command_name);
+            return display_page(formatted, options);
+        },
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    // Feed the formatted output into the command on a separate thread while its output is being displayed.
+    let mut processor_stdin = processor.stdin.take().unwrap();
+    let feeder = std::thread::spawn(move || io::copy(&mut formatted, &mut processor_stdin));
+
+    display_page(processor.stdout.take().unwrap(), options)?;
+
+    match feeder.join() {
+        Ok(Ok(_)) => {},
+        // The command stops reading when the pager is closed early.
+        Ok(Err(e)) if e.kind() == ErrorKind::BrokenPipe => {},
+        Ok(Err(e)) => error!("Error post-processing output with {}: {}", command_name, e),
+        Err(_) => error!("Error post-processing output with {}", command_name),
+    }
+    processor.wait()?;
+
+    Ok(())
+}
+
+// Format the contents with groff, or fall back to the lightly macro-stripped source if groff isn't installed.
+fn render_contents(contents: &str) -> BoxResult<Vec<u8>> {
+    match format_page(contents, "utf8") {
+        Ok(output) => Ok(output),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("manr: warning: {}, displaying unformatted page source", formatter_not_found());
+            Ok(strip_troff(contents).into_bytes())
+        },
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+// Colour the bold and underlined text grotty marks with SGR escapes, such as headings and arguments,
+// ending each colour along with the bold or underline.
+fn colorize(formatted: &[u8]) -> Vec<u8> {
+    String::from_utf8_lossy(formatted)
+        .replace("\x1b[1m", &format!("\x1b[1;{}m", COLOR_BOLD))
+        .replace("\x1b[22m", "\x1b[22;39m")
+        .replace("\x1b[4m", &format!("\x1b[4;{}m", COLOR_UNDERLINE))
+        .replace("\x1b[24m", "\x1b[24;39m")
+        .into_bytes()
+}
+
+// Remove the terminal escape sequences and overstrike characters grotty uses for bold and underlined text.
+fn plain_text(formatted: &str) -> String {
+    let escapes = Regex::new(r"\x1b\[[0-9;]*[A-Za-z]|\x1b\][^\x1b\x07]*(\x07|\x1b\\)").unwrap();
+    let overstrikes = Regex::new(r".\x08").unwrap();
+
+    overstrikes.replace_all(&escapes.replace_all(formatted, ""), "").to_string()
+}
+
+// Render a troff file that isn't in the index, reading from stdin when the path is "-", in the format set by the options.
+// Paths can be relative or absolute, and gzip, bzip2 or xz compressed input is extracted first. Formatted text is shown in the pager only when stdout is a terminal,
+// otherwise the output is written straight to stdout so it can be redirected or piped in build scripts.
+fn local_file(path: &str, options: &Options) -> BoxResult<()> {
+    let mut bytes = Vec::new();
+    if path == "-" {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        bytes = open_file(path.to_string()).map_err(|e| format!("manr: cannot open {}: {}", path, e))?;
+    }
+
+    if compression(&bytes, path).is_some() {
+        let mut extracted = Vec::new();
+        decompressing_reader(&bytes[..], path).read_to_end(&mut extracted)?;
+        bytes = extracted;
+    }
+    let contents = String::from_utf8_lossy(&bytes).to_string();
+
+    let format = options.format.clone().unwrap_or_else(|| "text".to_string());
+    let (device, _) = output_format(&format)?;
+
+    if device == "utf8" {
+        let formatted = render_contents(&contents)?;
+        if io::stdout().is_terminal() {
+            display_page(&formatted[..], options)?;
+        } else {
+            print!("{}", plain_text(&String::from_utf8_lossy(&formatted)));
+        }
+    } else {
+        let formatted = match format_page(&contents, device) {
+            Ok(formatted) => formatted,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(format!("manr: {}, it is required to render {}", formatter_not_found(), format).into()),
+            Err(e) => return Err(Box::new(e)),
+        };
+        io::stdout().write_all(&formatted)?;
+    }
+
+    Ok(())
+}
+
+// Render two page files and print a unified diff of their text, returning an error if they differ.
+fn compare_pages(old_path: String, new_path: String) -> BoxResult<()> {
+    let old_contents = decompress(old_path.clone(), ErrorAction::Fail)?;
+    let new_contents = decompress(new_path.clone(), ErrorAction::Fail)?;
+    let old_text = plain_text(&String::from_utf8_lossy(&render_contents(&old_contents)?));
+    let new_text = plain_text(&String::from_utf8_lossy(&render_contents(&new_contents)?));
+
+    if old_text == new_text {
+        println!("Pages {} and {} are identical", old_path, new_path);
+        return Ok(());
+    }
+
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    print!("{}", diff.unified_diff().header(&old_path, &new_path));
+
+    Err(format!("manr: pages {} and {} differ", old_path, new_path).into())
+}
+
+// Render every indexed page, or only those in the sections option, in parallel into separate files in the output directory.
+// Files are named after the page and section (ie: name.1.html) and a summary of the files written is printed at the end.
+fn export_all(options: &Options) -> BoxResult<()> {
+    let format = options.format.clone().unwrap_or_else(|| "text".to_string());
+    let (device, extension) = output_format(&format)?;
+    let output_dir = PathBuf::from(options.output_dir.clone().ok_or("manr: option '--export-all' requires '--output-dir <dir>'")?);
+    fs::create_dir_all(&output_dir)?;
+
+    let mut pages = all_pages()?;
+    pages.retain(|cache| in_sections(cache, options));
+    // Only export one path for each page and section, preferring the user's languages and then earlier manual directories.
+    let roots = man_roots()?;
+    let languages = preferred_languages();
+    let mut ranked: Vec<((usize, usize), Cache)> = pages.into_iter()
+        .filter_map(|cache| {
+            let (root_rank, root) = page_root(&cache.file_path, &roots);
+            language_rank(&cache.file_path, root, &languages).map(|rank| ((rank, root_rank), cache))
+        })
+        .collect();
+    ranked.sort_by(|a, b| a.1.page.cmp(&b.1.page).then_with(|| a.1.section.cmp(&b.1.section)).then_with(|| a.0.cmp(&b.0)));
+    ranked.dedup_by(|a, b| a.1.page == b.1.page && a.1.section == b.1.section);
+    let pages: Vec<Cache> = ranked.into_iter().map(|(_, cache)| cache).collect();
+
+    let total = pages.len();
+    let exported = std::sync::atomic::AtomicUsize::new(0);
+    let show_progress = io::stderr().is_terminal();
+
+    let failures: Vec<String> = pages.par_iter().filter_map(|cache| {
+        let result = export_page(cache, device, &output_dir.join(format!("{}.{}.{}", cache.page, cache.section, extension)));
+        let count = exported.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        if show_progress {
+            eprint!("\rExported {}/{}", count, total);
+        }
+
+        match result {
+            Ok(()) => None,
+            Err(e) => Some(format!("{} ({}): {}", cache.page, cache.section, e)),
+        }
+    }).collect();
+
+    if show_progress {
+        eprintln!();
+    }
+    for failure in failures.iter() {
+        error!("Error exporting {}", failure);
+    }
+    println!("Wrote {} {} files to {} ({} failed).", total - failures.len(), format, output_dir.display(), failures.len());
+
+    Ok(())
+}
+
+// Render a single indexed page for an output device and write it to a file.
+fn export_page(cache: &Cache, device: &str, output_path: &Path) -> Result<(), String> {
+    let contents = decompress(cache.file_path.to_string(), ErrorAction::Log).map_err(|e| e.to_string())?;
+    let formatted = format_page(&contents, device).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => formatter_not_found(),
+        _ => e.to_string(),
+    })?;
+
+    // Text output is saved without the terminal escape sequences used for bold and underlined text.
+    let output = if device == "utf8" {
+        plain_text(&String::from_utf8_lossy(&formatted)).into_bytes()
+    } else {
+        formatted
+    };
+
+    fs::write(output_path, output).map_err(|e| e.to_string())
+}
+
+// Build the groff command for an output device, such as "utf8" for the terminal, reading the page from stdin and writing to stdout.
+// Preprocessor flags from the page's hint line are passed along so only the ones it needs are run.
+// Another formatter set in the config, such as mandoc, is given its configured arguments and the output device only.
+// (Seems to have issue formatting numbered/nested lists.)
+fn groff_command(device: &str, preprocessors: &[&str]) -> Command {
+    let (formatter, formatter_args) = formatter_command();
+    let mut groff = Command::new(&formatter);
+    groff.args(&formatter_args);
+    if is_groff(&formatter) {
+        groff.arg("-mandoc")
+        .args(preprocessors);
+    }
+    groff.arg(format!("-T{}", device))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped());
+
+    groff
+}
+
+// Get the formatter program and its arguments from $MANR_FORMATTER, or else the formatter config key, or groff by default.
+fn formatter_command() -> (String, Vec<String>) {
+    let command_line = env::var("MANR_FORMATTER").ok()
+        .or_else(|| config_setting("formatter").and_then(|value| value.as_str().map(|s| s.to_string())))
+        .filter(|command_line| !command_line.trim().is_empty())
+        .unwrap_or_else(|| "groff".to_string());
+    let mut words = command_line.split_whitespace().map(|word| word.to_string());
+    let formatter = words.next().unwrap_or_else(|| "groff".to_string());
+
+    (formatter, words.collect())
+}
+
+// Describe the configured formatter being missing and how to fix it (ie: "groff not found (install groff or set MANR_FORMATTER)").
+fn formatter_not_found() -> String {
+    let (formatter, _) = formatter_command();
+
+    format!("{} not found (install {} or set MANR_FORMATTER)", formatter, formatter)
+}
+
+// Check whether a formatter program is groff, which takes the man macros, preprocessor flags and number registers.
+fn is_groff(formatter: &str) -> bool {
+    Path::new(formatter).file_name().is_some_and(|name| name == "groff")
+}
+
+// Get the groff flags for the preprocessors listed in a page's first line hint, such as '\" t for tbl or '\" te for tbl and eqn.
+// Lines that aren't a hint, like ordinary comments, request no preprocessors.
+fn preprocessor_flags(first_line: &str) -> Vec<&'static str> {
+    let hint = match first_line.strip_prefix("'\\\"").or_else(|| first_line.strip_prefix(".\\\"")) {
+        Some(hint) => hint.trim(),
+        None => return Vec::new(),
+    };
+    if hint.is_empty() || !hint.chars().all(|c| "egprtv".contains(c)) {
+        return Vec::new();
+    }
+
+    let mut flags = Vec::new();
+    for letter in hint.chars() {
+        let flag = match letter {
+            'e' => "-e",
+            'g' => "-G",
+            'p' => "-p",
+            'r' => "-R",
+            't' => "-t",
+            // Groff has no vgrind preprocessor so the letter is ignored.
+            _ => continue,
+        };
+        if !flags.contains(&flag) {
+            flags.push(flag);
+        }
+    }
+
+    flags
+}
+
+// Get the groff output device and file extension for an output format name.
+fn output_format(format: &str) -> BoxResult<(&'static str, &'static str)> {
+    match format {
+        "text" | "txt" | "utf8" => Ok(("utf8", "txt")),
+        "html" => Ok(("html", "html")),
+        "ps" => Ok(("ps", "ps")),
+        "pdf" => Ok(("pdf", "pdf")),
+        _ => Err(format!("manr: unsupported format '{}', expected text, html, ps or pdf", format).into()),
+    }
+}
+
+// Load extracted gzip contents into groff application for an output device and return the formatted output.
+fn format_page(contents: &str, device: &str) -> io::Result<Vec<u8>> {
+    let preprocessors = preprocessor_flags(contents.lines().next().unwrap_or(""));
+    let mut groff = groff_command(device, &preprocessors).spawn()?;
+
+    // Write the contents on a separate thread while reading the output, so large pages can't fill the pipe and block groff.
+    let mut stdin = groff.stdin.take().unwrap();
+    let input = contents.as_bytes().to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = groff.wait_with_output()?;
+    writer.join().unwrap_or(Ok(()))?;
+
+    Ok(output.stdout)
+}
+
+// Pass a formatted document into the less viewer application (or the pager set in the options),
+// or print it straight to stdout if the pager isn't installed, stdout isn't a terminal or the no pager option is set.
+// The document is copied through in chunks rather than being read into memory first.
+fn display_page<R: Read>(mut formatted: R, options: &Options) -> BoxResult<()> {
+    if !uses_pager(options) {
+        // Ignore a broken pipe caused by a command reading the output, such as head, exiting early.
+        if let Err(e) = io::copy(&mut formatted, &mut io::stdout()) {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(Box::new(e));
+            }
+        }
+        return Ok(());
+    }
+
+    let (pager_name, pager_args) = pager_command(options);
+
+    let less = Command::new(&pager_name)
+    .args(pager_args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::inherit())
+    .spawn();
+
+    match less {
+        Ok(mut less) => {
+            {
+                let stdin = less.stdin.as_mut().unwrap();
+                // Ignore a broken pipe caused by quitting less before the whole page has been read.
+                if let Err(e) = io::copy(&mut formatted, stdin) {
+                    if e.kind() != ErrorKind::BrokenPipe {
+                        return Err(Box::new(e));
+                    }
+                }
+            }
+            less.wait()?;
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("manr: warning: {} not found (install {} or set PAGER), printing page to stdout", pager_name, pager_name);
+            io::copy(&mut formatted, &mut io::stdout())?;
+        },
+        Err(e) => return Err(Box::new(e)),
+    }
+
+    Ok(())
+}
+
+// Check whether pages are shown in a pager, which is only when stdout is a terminal and the no pager option isn't set.
+fn uses_pager(options: &Options) -> bool {
+    !options.no_pager && io::stdout().is_terminal()
+}
+
+// Get the pager program and its arguments, or less by default.
+// The pager option comes first, then $PAGER and then the pager config key.
+fn pager_command(options: &Options) -> (String, Vec<String>) {
+    let pager_command = options.pager.clone()
+        .or_else(|| env::var("PAGER").ok())
+        .or_else(|| config_setting("pager").and_then(|value| value.as_str().map(|s| s.to_string())))
+        .filter(|pager_command| !pager_command.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut pager_args = pager_command.split_whitespace();
+    let pager_name = pager_args.next().unwrap_or("less").to_string();
+    let mut pager_args: Vec<String> = pager_args.map(|arg| arg.to_string()).collect();
+    if options.number {
+        pager_args.extend(line_number_args(&pager_name).into_iter().map(|arg| arg.to_string()));
+    }
+
+    (pager_name, pager_args)
+}
+
+// The arguments that make a pager show line numbers, or none for pagers without an equivalent option.
+fn line_number_args(pager_name: &str) -> Vec<&'static str> {
+    let name = Path::new(pager_name).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    match name.as_str() {
+        "less" => vec!["-N"],
+        "vi" | "vim" | "nvim" | "view" => vec!["-c", "set number"],
+        _ => {
+            debug!("{} has no line number option, showing page without line numbers", pager_name);
+            Vec::new()
+        },
+    }
+}
+
+// Run the configured formatter over every indexed page in parallel and report the pages that produce warnings or errors.
+// Each reported page is listed with the first line groff wrote to stderr.
+fn troff_lint() -> BoxResult<()> {
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let mut paths: Vec<String> = index.values().map(|cache| cache.file_path.to_string()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let results: Vec<(String, io::Result<Option<String>>)> = paths.par_iter()
+        .map(|path| (path.to_string(), lint_page(path)))
+        .collect();
+
+    let mut reported = 0;
+    for (path, result) in results {
+        match result {
+            Ok(Some(warning)) => {
+                println!("{}: {}", path, warning);
+                reported += 1;
+            },
+            Ok(None) => {},
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(format!("manr: {}, it is required to lint pages", formatter_not_found()).into());
+            },
+            Err(e) => error!("Error linting {}: {}", path, e),
+        }
+    }
+
+    println!("{} of {} pages produced groff warnings or errors.", reported, paths.len());
+
+    Ok(())
+}
+
+// Format a page with the configured formatter, with all groff warnings enabled and output discarded, returning the first line written to stderr.
+fn lint_page(path: &str) -> io::Result<Option<String>> {
+    // Pages that can't be extracted are logged by decompress and skipped.
+    let contents = match decompress(path.to_string(), ErrorAction::Log) {
+        Ok(contents) if !contents.is_empty() => contents,
+        _ => return Ok(None),
+    };
+
+    let preprocessors = preprocessor_flags(contents.lines().next().unwrap_or(""));
+    let mut command = groff_command("utf8", &preprocessors);
+    if is_groff(&command.get_program().to_string_lossy()) {
+        command.args(["-ww", "-z"]);
+    }
+    let mut groff = command
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+    // Feed the page into groff on a separate thread while its warnings are being read, so neither side can block on a full pipe.
+    let mut stdin = groff.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(contents.as_bytes()));
+
+    let output = groff.wait_with_output()?;
+    match writer.join() {
+        Ok(Ok(_)) => {},
+        // Groff stops reading if it exits before the end of the page.
+        Ok(Err(e)) if e.kind() == ErrorKind::BrokenPipe => {},
+        Ok(Err(e)) => return Err(e),
+        Err(_) => error!("Error linting {}", path),
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    Ok(stderr.lines().find(|line| !line.trim().is_empty()).map(|line| line.to_string()))
+}
+
+// Lightly strip troff/mandoc macros from a page source so it stays readable without a formatter.
+fn strip_troff(contents: &str) -> String {
+    let mut text = String::new();
+    // The URL or email address of an open .UR or .MT link, shown after the link text.
+    let mut link: Option<String> = None;
+
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+
+        // Skip comment lines entirely.
+        if trimmed.starts_with(".\\\"") || trimmed.starts_with("'\\\"") || trimmed.starts_with("\\\"") {
+            continue;
+        }
+
+        // Control lines begin with "." or "'" and are either kept as text or dropped depending on the macro.
+        if let Some(request) = trimmed.strip_prefix('.').or_else(|| trimmed.strip_prefix('\'')) {
+            let (name, rest) = request.trim_start().split_once(char::is_whitespace).unwrap_or((request.trim_start(), ""));
+            let arguments = macro_arguments(rest);
+
+            match name {
+                // Section headings are shown in capitals on their own line.
+                "SH" | "Sh" => {
+                    text.push('\n');
+                    text.push_str(&unescape_troff(&arguments.join(" ")).to_uppercase());
+                    text.push('\n');
+                },
+                "SS" | "Ss" => {
+                    text.push('\n');
+                    text.push_str(&unescape_troff(&arguments.join(" ")));
+                    text.push('\n');
+                },
+                // Paragraph and line breaks.
+                "PP" | "LP" | "P" | "Pp" | "sp" | "TP" | "IP" | "HP" => {
+                    text.push('\n');
+                    if !arguments.is_empty() && name == "IP" {
+                        text.push_str(&unescape_troff(&arguments[0]));
+                        text.push('\n');
+                    }
+                },
+                // Font macros keep their text, with the alternating forms joining their words together.
+                "B" | "I" | "SM" | "SB" | "Nm" | "Ar" | "Nd" | "Dl" => {
+                    text.push_str(&unescape_troff(&arguments.join(" ")));
+                    text.push('\n');
+                },
+                "BR" | "RB" | "IR" | "RI" | "BI" | "IB" => {
+                    text.push_str(&unescape_troff(&arguments.concat()));
+                    text.push('\n');
+                },
+                // Links keep their text followed by the address, with any trailing punctuation from the closing macro.
+                "UR" | "MT" => {
+                    link = arguments.first().cloned();
+                },
+                "UE" | "ME" => {
+                    if let Some(address) = link.take() {
+                        if text.ends_with('\n') {
+                            text.pop();
+                        }
+                        text.push_str(&format!(" <{}>", address));
+                        text.push_str(&unescape_troff(&arguments.concat()));
+                        text.push('\n');
+                    }
+                },
+                // Drop all other requests and macros.
+                _ => {},
+            }
+        } else {
+            text.push_str(&unescape_troff(line));
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+// Split the arguments of a troff macro on whitespace while keeping double quoted arguments together.
+fn macro_arguments(rest: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in rest.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    arguments.push(current.clone());
+                    current.clear();
+                }
+            },
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+// Convert common troff escape sequences into plain text and remove font changes.
+fn unescape_troff(text: &str) -> String {
+    let font = Regex::new(r"\\f(\[[^\]]*\]|\(..|.)").unwrap();
+    let mut result = font.replace_all(text, "").to_string();
+
+    let escapes = [
+        ("\\-", "-"), ("\\(aq", "'"), ("\\(dq", "\""), ("\\(lq", "\u{201c}"), ("\\(rq", "\u{201d}"),
+        ("\\(em", "\u{2014}"), ("\\(en", "\u{2013}"), ("\\(bu", "\u{2022}"), ("\\(co", "\u{a9}"),
+        ("\\~", " "), ("\\ ", " "), ("\\&", ""), ("\\c", ""), ("\\,", ""), ("\\/", ""), ("\\%", ""),
+        ("\\|", ""), ("\\^", ""), ("\\*(Aq", "'"), ("\\*(aq", "'"), ("\\e", "\\"),
+    ];
+    for (escape, replacement) in escapes.iter() {
+        result = result.replace(escape, replacement);
+    }
+
+    result
+}
+
+// Open a file and read its contents into a Vector.
+fn open_file(path: String) -> BoxResult<Vec<u8>> {
+    if let Some((archive, member)) = archive_member_path(&path) {
+        return Ok(read_archive_member(archive, member)?);
+    }
+
+    let mut file = File::open(path.clone())?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+// Check whether a manual root is a tar archive of pages rather than a directory.
+fn is_archive(path: &str) -> bool {
+    path.ends_with(".tar") || path.ends_with(".tar.gz") || path.ends_with(".tgz")
+}
+
+// Split the path of a page stored in an archive into the archive path and the member path within it.
+fn archive_member_path(path: &str) -> Option<(&str, &str)> {
+    let (archive, member) = path.split_once("!/")?;
+
+    if is_archive(archive) {
+        Some((archive, member))
+    } else {
+        None
+    }
+}
+
+// Open a tar archive for reading its members in order, decompressing it as it is read if it is gzip compressed.
+fn open_archive(archive: &str) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = BufReader::new(File::open(archive)?);
+    let reader: Box<dyn Read> = if archive.ends_with(".tar") {
+        Box::new(file)
+    } else {
+        Box::new(GzDecoder::new(file))
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+// Read the contents of a single member of a tar archive, stopping as soon as it is found.
+fn read_archive_member(archive: &str, member_path: &str) -> io::Result<Vec<u8>> {
+    let mut tar = open_archive(archive)?;
+
+    for member in tar.entries()? {
+        let mut member = member?;
+        if member.path()?.to_string_lossy().trim_start_matches("./") == member_path {
+            let mut contents = Vec::new();
+            member.read_to_end(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+
+    Err(io::Error::new(ErrorKind::NotFound, format!("{} not found in {}", member_path, archive)))
+}
+
+// Print or log an error opening a manual file depending on setting of ErrorAction.
+fn report_open_error(err: &io::Error, page: &str, section: &str, errors: &ErrorAction) {
+    if *errors == ErrorAction::Fail {
+        match err.kind() {
+            ErrorKind::NotFound => println!("No manual entry for {} in section {}", page, section),
+            ErrorKind::PermissionDenied => println!("Permission denied for {} in section {}", page, section),
+            _ => println!("Error opening file {:?}", err),
+        }
+        page_failed();
+    } else {
+        match err.kind() {
+            ErrorKind::NotFound => error!("No manual entry for {} in section {}", page, section),
+            ErrorKind::PermissionDenied => error!("Permission denied for {} in section {}", page, section),
+            _ => error!("Error opening file {:?}", err),
+        }
+    }
+}
+
+// Split a file path into its page name and section by removing the .gz extension and splitting at the last "." character.
+fn page_and_section(path: &str) -> (String, String) {
+    let filename = strip_compression_extension(path.split('/').next_back().unwrap_or(path));
+
+    match filename.rsplit_once('.') {
+        Some((page, section)) => (page.to_string(), section.to_string()),
+        None => (filename.to_string(), String::new()),
+    }
+}
+
+// Open a compressed or uncompressed manual file as a stream of its decompressed contents, so large pages don't need to be held in memory.
+// Errors opening the file are handled the same way as in decompress.
+fn page_reader(path: String, errors: ErrorAction) -> BoxResult<Box<dyn Read + Send>> {
+    let (page, section) = page_and_section(&path);
+
+    // Pages in archives are read into memory on their own, leaving the rest of the archive packed.
+    let opened = match archive_member_path(&path) {
+        Some((archive, member)) => read_archive_member(archive, member)
+            .map(|bytes| (bytes.len() as u64, Box::new(io::Cursor::new(bytes)) as Box<dyn Read + Send>)),
+        None => File::open(&path)
+            .and_then(|file| Ok((file.metadata()?.len(), Box::new(file) as Box<dyn Read + Send>))),
+    };
+
+    let (length, file) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            report_open_error(&e, &page, &section, &errors);
+            if errors == ErrorAction::Fail {
+                return Err(Box::new(ReportedError));
+            }
+            return Err(Box::new(e));
+        },
+    };
+
+    // An empty file opens successfully but contains no page, which can be left behind by interrupted installs.
+    if length == 0 {
+        let message = format!("Empty page file for {} in section {}", page, section);
+        if errors == ErrorAction::Log {
+            error!("{}", message);
+        }
+        return Err(message.into());
+    }
+
+    // Uncompressed pages (ie: name.1) are passed through as they are.
+    let mut reader = BufReader::new(file);
+    let compressed = compression(reader.fill_buf()?, &path);
+    Ok(match compressed {
+        Some(Compression::Gzip) => Box::new(GzDecoder::new(reader)),
+        Some(Compression::Bzip2) => Box::new(BzDecoder::new(reader)),
+        Some(Compression::Xz) => Box::new(XzDecoder::new(reader)),
+        None => Box::new(reader),
+    })
+}
+
+// Compression formats that manual pages can be stored in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+}
+
+// Find how a page file is compressed from its first bytes, or from its extension if they aren't recognised.
+// Returns None for uncompressed pages.
+fn compression(start: &[u8], path: &str) -> Option<Compression> {
+    if start.starts_with(&GZIP_MAGIC) {
+        Some(Compression::Gzip)
+    } else if start.starts_with(&BZIP2_MAGIC) {
+        Some(Compression::Bzip2)
+    } else if start.starts_with(&XZ_MAGIC) {
+        Some(Compression::Xz)
+    } else if path.ends_with(".gz") {
+        Some(Compression::Gzip)
+    } else if path.ends_with(".bz2") {
+        Some(Compression::Bzip2)
+    } else if path.ends_with(".xz") {
+        Some(Compression::Xz)
+    } else {
+        None
+    }
+}
+
+// Wrap the contents of a page file in the decoder for its compression, or read them as they are if uncompressed.
+fn decompressing_reader<'a>(contents: &'a [u8], path: &str) -> Box<dyn Read + 'a> {
+    match compression(contents, path) {
+        Some(Compression::Gzip) => Box::new(GzDecoder::new(contents)),
+        Some(Compression::Bzip2) => Box::new(BzDecoder::new(contents)),
+        Some(Compression::Xz) => Box::new(XzDecoder::new(contents)),
+        None => Box::new(contents),
+    }
+}
+
+// Remove the compression extension from a page filename, so "name.1.gz", "name.1.bz2" and "name.1.xz" all become "name.1".
+fn strip_compression_extension(filename: &str) -> &str {
+    PAGE_EXTENSIONS.iter()
+        .find_map(|extension| filename.strip_suffix(&format!(".{}", extension)))
+        .unwrap_or(filename)
+}
+
+// Decode extracted page contents as UTF-8, or guess the legacy encoding of pages that aren't valid UTF-8 and convert them.
+// Returns the contents and whether they were converted.
+fn decode_page(bytes: &[u8], page: &str, section: &str) -> (String, bool) {
+    match std::str::from_utf8(bytes) {
+        Ok(contents) => (contents.to_string(), false),
+        Err(_) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(bytes, true);
+            let encoding = detector.guess(None, false);
+            debug!("Detected {} encoding for {} in section {}", encoding.name(), page, section);
+
+            let (contents, _, _) = encoding.decode(bytes);
+            (contents.into_owned(), true)
+        },
+    }
+}
+
+// Extract gzip, bzip2 or xz compressed files, or read uncompressed files, into String contents.
+pub fn decompress(path: String, errors: ErrorAction) -> BoxResult<String> {
+    // Split file path from filename and format name by removing the compression extension and splitting at last "." character.
+    // Files named without a section, such as those given to the compare command, have an empty section.
+    let (page, section) = page_and_section(&path);
+    let (page, section) = (page.as_str(), section.as_str());
+
+    // Open the file path and read its contents into a new Vector. 
+    let file_result = open_file(path.clone());
+    let file_opened = file_result.is_ok();
+    let mut contents = Vec::new();
+
+    // Match any errors to their kind and either print and return a reported error or log and continue, depending on setting of ErrorAction.
+    match file_result {
+        Ok(file) => {
+            contents = file;
+        },
+        Err(e) => {
+            // Downcast boxed error to type that implements the std Error trait.
+            if let Some(err) = e.downcast_ref::<io::Error>() {
+                report_open_error(err, page, section, &errors);
+            }
+            if errors == ErrorAction::Fail {
+                return Err(Box::new(ReportedError));
+            }
+        }
+    };
+
+    // An empty file opens successfully but contains no page, which can be left behind by interrupted installs.
+    if file_opened && contents.is_empty() {
+        let message = format!("Empty page file for {} in section {}", page, section);
+        if errors == ErrorAction::Log {
+            error!("{}", message);
+        }
+        return Err(message.into());
+    }
+
+    // Extract the contents of the opened file with the decoder for its compression, reading uncompressed pages (ie: name.1) as they are.
+    let mut gzip = decompressing_reader(&contents, &path);
+    let mut gzip_contents = Vec::new();
+    // Check if the file extracted successfully and if not log the error and return it, rather than an empty page.
+    // When failing the error has already been logged, so it isn't printed again.
+    if let Err(e) = gzip.read_to_end(&mut gzip_contents) {
+        let message = format!("Error extracting gzip file for {} in section {}: {}", page, section, e);
+        error!("{}", message);
+        if errors == ErrorAction::Fail {
+            page_failed();
+            return Err(Box::new(ReportedError));
+        }
+        return Err(message.into());
+    }
+
+    // Pages in legacy encodings are converted as with the encoding detection option, so their text can still be read and indexed.
+    let (gzip_contents, _) = decode_page(&gzip_contents, page, section);
+
+    Ok(gzip_contents)
+}
+
+// Recursively list and sort all sections within a configured search directory.
+// Symbolic links are only followed into other directories when follow_links is set.
+// Directories that can't be read are skipped with a logged error, so a search directory without permission doesn't silently come back empty.
+fn list_all_sections(default_path: &str, follow_links: bool) -> BoxResult<Vec<DirEntry>> {
+    let suffix = page_file_suffix()?;
+
+    // List all files (including symbolic links) in a search directory adhering to the regex pattern.
+    // Missing directories are common in $MANPATH, so they are skipped without an error.
+    let mut files: Vec<DirEntry> = WalkDir::new(default_path)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_map(|result| match result {
+            Ok(entry) => Some(entry),
+            Err(e) if e.io_error().is_some_and(|e| e.kind() == ErrorKind::NotFound) => None,
+            Err(e) => {
+                let path = e.path().map_or(default_path.to_string(), |path| path.display().to_string());
+                match e.io_error() {
+                    Some(io_error) => error!("Skipping {}, which can't be read: {}", path, io_error),
+                    None => error!("Skipping {}: {}", path, e),
+                }
+                None
+            },
+        })
+        .filter(|result| result.file_type().is_file())
+        .filter(|result| suffix.is_match(result.file_name().to_string_lossy().as_ref()))
+        .collect();
+    
+    // Sort a page's sections in a ascending order according to the numeric range of the suffix.
+    files.sort_by_key(|entry| {
+        let sort_sections = suffix.captures(entry.file_name().to_string_lossy().as_ref()).unwrap()[1].parse::<u32>().unwrap();
+        sort_sections
+    });
+
+    Ok(files)
+}
+
+// A regex for a suffix covering filenames formatted like "name.1.gz" or "name.1ssl.gz" with a numeric range of 1-9,
+// limited to the configured page extensions, or uncompressed pages formatted like "name.1".
+fn page_file_suffix() -> BoxResult<Regex> {
+    let extensions: Vec<String> = page_extensions().iter().map(|extension| regex::escape(extension)).collect();
+
+    Ok(Regex::new(&format!(r"\.([1-9])(?:[a-zA-Z]*)?(?:\.(?:{}))?$", extensions.join("|")))?)
+}
+
+// Format filename and short description for displaying in terminal (ie: name (1) - description text).
+// Descriptions found in an existing whatis database are used instead of extracting them from the page.
+// The original name stored in the gzip header is preferred over the filename when enabled in the settings.
+fn format_filename_and_description(path: String, whatis: &HashMap<(String, String), String>, settings: &IndexSettings) -> BoxResult<String> {
+    // Split path from filename and format filenames by removing .gz extension and splitting at last "." character. Then add relevant description.
+    let (page, section) = page_and_section(&path);
+
+    // Pages renamed during packaging still have their original name in the gzip header.
+    // The header often leaves out the section suffix the page was installed with (ie: name.1 for name.1ssl.gz), so the full section is kept.
+    let header_name = if settings.use_gzip_header_names { gzip_header_name(&path) } else { None };
+    let (page, section) = match header_name {
+        Some((page, header_section)) if section.to_lowercase().starts_with(&header_section.to_lowercase()) => (page, section),
+        Some((page, header_section)) => (page, header_section),
+        None => (page, section),
+    };
+    let page = if settings.strip_version_suffixes { strip_version_suffix(&page) } else { page };
+
+    let (description, names) = match whatis.get(&(page.to_string(), section.to_string())) {
+        Some(description) => (description.to_string(), Vec::new()),
+        None => get_description(path.clone())?,
+    };
+
+    Ok(format!("{} ({}) - {}", page_with_names(page, names), section, description))
+}
+
+// Join a page name with the other names listed in its NAME section, separated by commas without spaces (ie: gzip,gunzip,zcat),
+// so the page is indexed under each of them.
+fn page_with_names(page: String, names: Vec<String>) -> String {
+    let mut pages = vec![page];
+    for name in names {
+        if !pages.contains(&name) {
+            pages.push(name);
+        }
+    }
+
+    pages.join(",")
+}
+
+// Remove a version number from the end of a page name, so a page installed as "foo-1.2.1.gz" (named "foo-1.2") is indexed as "foo".
+// Only a dash followed by dot separated numbers is removed, so names like "python3.11" or "gcc-12" are left unchanged.
+fn strip_version_suffix(page: &str) -> String {
+    let version = Regex::new(r"^(.+?)-v?[0-9]+(?:\.[0-9]+)+$").unwrap();
+
+    match version.captures(page) {
+        Some(captures) => captures[1].to_string(),
+        None => page.to_string(),
+    }
+}
+
+// Get the page name and section from the original filename stored in a gzip file's header, if it has one
+// formatted like a manual page filename (ie: name.1).
+fn gzip_header_name(path: &str) -> Option<(String, String)> {
+    let file = File::open(path).ok()?;
+    let mut gzip = GzDecoder::new(BufReader::new(file));
+
+    // The whole header, including the stored name, is parsed before the first byte of the contents is returned.
+    // A page with no contents still has a complete header, while a truncated header leaves none to read the name from.
+    let mut buffer = [0; 1];
+    match gzip.read_exact(&mut buffer) {
+        Ok(()) => {},
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {},
+        Err(_) => return None,
+    }
+
+    let stored_name = String::from_utf8_lossy(gzip.header()?.filename()?).to_string();
+    let captures = page_name_format().captures(stored_name.trim_end_matches(".gz"))?;
+
+    Some((captures[1].to_string(), captures[2].to_string()))
+}
+
+// The pattern of a page name followed by its section after a dot (ie: "printf.3" or "printf.3p"), compiled once as it is matched
+// against every page file while indexing.
+fn page_name_format() -> &'static Regex {
+    static PAGE_NAME_FORMAT: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+
+    PAGE_NAME_FORMAT.get_or_init(|| Regex::new(r"^(.+)\.([1-9][a-zA-Z]*)$").unwrap())
+}
+
+// Split a page given with its section after a dot (ie: "printf.3" or "printf.3p") into the page and section,
+// unless a page has that whole name. Names with other dots, such as "python3.11", are left whole.
+fn inline_section(arg: &str, files: &HashMap<u32, Cache>) -> BoxResult<Option<(String, String)>> {
+    let captures = match page_name_format().captures(arg) {
+        Some(captures) => captures,
+        None => return Ok(None),
+    };
+    if !matching_paths(arg, &[], files)?.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some((captures[1].to_string(), captures[2].to_string())))
+}
+
+// Find and run/display the first section in the section order (or the lowest section number) if none is provided by user.
+// If a list of sections is given in the options the first of those sections with the page is used instead.
+// With the all option every section found is run in that order.
+fn first_section(page: String, options: &Options) -> BoxResult<()> {
+    let files: HashMap<u32, Cache> = page_index(&page, options)?;
+    first_section_in(page, &files, options)
+}
+
+// Find and run/display the first section of a page as with first_section, using an index that has already been loaded.
+fn first_section_in(page: String, files: &HashMap<u32, Cache>, options: &Options) -> BoxResult<()> {
+    let mut page = page;
+    let mut results = matching_paths(&page, &options.sections, files)?;
+
+    // In fuzzy mode a page that isn't found is replaced by the closest matching page name, which needs the index.
+    if results.is_empty() && options.fuzzy && !options.no_index {
+        if let Some(matched) = fuzzy_page(&page)? {
+            page = matched;
+            results = matching_paths(&page, &options.sections, files)?;
+        }
+    }
+
+    // Check at least one page section exists before trying to run the first file path.
+    if results.is_empty() {
+        let mut man_args: Vec<String> = Vec::new();
+        if options.where_path {
+            man_args.push("-w".to_string());
+        }
+        if !options.sections.is_empty() {
+            man_args.push("-S".to_string());
+            man_args.push(options.sections.join(":"));
+        }
+        man_args.push(page.to_string());
+
+        if delegate_to_system_man(man_args)? {
+            return Ok(());
+        } else if options.sections.is_empty() {
+            match suggest_packages(&page) {
+                Some(packages) => println!("No manual entry for {}. It may be provided by package: {}.", page, packages),
+                None => println!("No manual entry for {}", page),
+            }
+            page_failed();
+        } else {
+            println!("No manual entry for {} in section {}", page, options.sections.join(","));
+            page_failed();
+        }
+    } else if options.where_path {
+        // Every section of the page is printed in order, not just the one that would be opened.
+        for file_path in results {
+            println!("{}", file_path);
+        }
+        page_shown();
+    } else if options.all {
+        for file_path in results {
+            run(file_path, options)?;
+        }
+    } else {
+        let first_file = results[0].to_string();
+        run(first_file, options)?;
+    }
+
+    Ok(())
+}
+
+// Load the index cache for looking up a page. With the no index option, or when the index can't be read or rebuilt,
+// the files of the page are found by searching the manual directories directly instead.
+fn page_index(page: &str, options: &Options) -> BoxResult<HashMap<u32, Cache>> {
+    if options.no_index {
+        return filesystem_index(page);
+    }
+
+    match deserialise_index() {
+        Ok(index) => Ok(index),
+        Err(e) => {
+            eprintln!("manr: warning: the index is unavailable ({}), searching the manual directories directly", e);
+            filesystem_index(page)
+        },
+    }
+}
+
+// Find the files of a page in the manual directories and any extra directories, matching filenames with the same pattern
+// used when indexing, as entries without descriptions in place of the index. Archives aren't searched.
+fn filesystem_index(page: &str) -> BoxResult<HashMap<u32, Cache>> {
+    filesystem_pages(|file_page| file_page.to_lowercase() == page.to_lowercase())
+}
+
+// Find the files in the manual directories and any extra directories whose page names are accepted by a filter,
+// as entries without descriptions in place of the index. Archives aren't searched.
+fn filesystem_pages<F: Fn(&str) -> bool>(accept: F) -> BoxResult<HashMap<u32, Cache>> {
+    let follow_symlinks = index_settings().follow_symlinks;
+    let mut index: HashMap<u32, Cache> = HashMap::new();
+
+    for root in man_roots()?.into_iter().chain(extra_roots()).filter(|root| !is_archive(root)) {
+        for file in list_all_sections(&root, follow_symlinks)? {
+            let file_path = file.path().to_string_lossy().to_string();
+            let (file_page, section) = page_and_section(&file_path);
+            if accept(&file_page) {
+                let id = index.len() as u32 + 1;
+                index.insert(id, Cache { id, page: file_page, section, description: String::new(), file_path, mtime: 0 });
+            }
+        }
+    }
+
+    Ok(index)
+}
+
+// Find the indexed page name that best fuzzy matches a name, if it scores at least the configured threshold.
+// When several names score about as well as the best the user picks one, or none is chosen if stdin isn't a terminal.
+fn fuzzy_page(page: &str) -> BoxResult<Option<String>> {
+    let threshold = config_setting("fuzzy_threshold").and_then(|value| value.as_integer()).unwrap_or(DEFAULT_FUZZY_THRESHOLD);
+    let matcher = SkimMatcherV2::default();
+
+    let mut names: Vec<String> = deserialise_index()?.into_values().map(|cache| cache.page).collect();
+    names.sort();
+    names.dedup();
+
+    let mut matches: Vec<(i64, String)> = names.into_iter()
+        .filter_map(|name| matcher.fuzzy_match(&name, page).map(|score| (score, name)))
+        .filter(|(score, _)| *score >= threshold)
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let best_score = match matches.first() {
+        Some((score, _)) => *score,
+        None => return Ok(None),
+    };
+    let candidates: Vec<String> = matches.into_iter()
+        .take_while(|(score, _)| best_score - score <= FUZZY_AMBIGUITY_MARGIN)
+        .map(|(_, name)| name)
+        .collect();
+
+    if candidates.len() == 1 {
+        return Ok(candidates.into_iter().next());
+    }
+    if !io::stdin().is_terminal() {
+        println!("manr: {} is ambiguous, did you mean: {}?", page, candidates.join(", "));
+        return Ok(None);
+    }
+
+    for (number, name) in candidates.iter().enumerate() {
+        println!("{}) {}", number + 1, name);
+    }
+    print!("Open which page for {}? [1-{}, Enter to cancel]: ", page, candidates.len());
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().parse::<usize>().ok()
+        .and_then(|number| number.checked_sub(1))
+        .and_then(|index| candidates.get(index).cloned()))
+}
+
+// Ask the system package database which packages provide a page, when the suggest_packages config key is set.
+// Uses apt-file or pacman, whichever is installed, and returns None if neither is or nothing provides the page.
+fn suggest_packages(page: &str) -> Option<String> {
+    if !config_setting("suggest_packages").and_then(|value| value.as_bool()).unwrap_or(false) {
+        return None;
+    }
+
+    let pattern = format!("usr/share/man/man[^/]*/{}\\.[^/]*$", regex::escape(page));
+    let output = if find_in_path("apt-file").is_some() {
+        Command::new("apt-file").args(["--package-only", "--regexp", "search", &pattern]).output().ok()?
+    } else if find_in_path("pacman").is_some() {
+        Command::new("pacman").args(["-F", "--quiet", "--regex", &pattern]).output().ok()?
+    } else {
+        return None;
+    };
+
+    // Both tools print one package per line, with pacman prefixing the repository (ie: extra/package).
+    let mut packages: Vec<String> = String::from_utf8_lossy(&output.stdout).lines()
+        .map(|line| line.trim().rsplit('/').next().unwrap_or("").to_string())
+        .filter(|package| !package.is_empty())
+        .collect();
+    packages.sort();
+    packages.dedup();
+
+    if packages.is_empty() {
+        None
+    } else {
+        Some(packages.join(", "))
+    }
+}
+
+// Open the lowest section of every page whose name matches a glob pattern, in alphabetical order.
+fn open_glob(pattern: String, options: &Options) -> BoxResult<()> {
+    let matcher = Glob::new(&pattern)?.compile_matcher();
+
+    // The index is loaded once and every matching page is looked up in it, or the manual directories are searched without it.
+    let search_directories = || filesystem_pages(|page| matcher.is_match(page));
+    let index: HashMap<u32, Cache> = if options.no_index {
+        search_directories()?
+    } else {
+        match deserialise_index() {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("manr: warning: the index is unavailable ({}), searching the manual directories directly", e);
+                search_directories()?
+            },
+        }
+    };
+
+    let mut pages: Vec<&str> = index.values()
+        .filter(|cache| matcher.is_match(&cache.page))
+        .map(|cache| cache.page.as_str())
+        .collect();
+    pages.sort();
+    pages.dedup();
+
+    let mut queue: Vec<QueuedPage> = Vec::new();
+    for page in pages {
+        if let Some(file_path) = matching_paths(page, &[], &index)?.into_iter().next() {
+            queue.push(QueuedPage::Path(file_path));
+        }
+    }
+
+    if queue.is_empty() {
+        println!("No manual entries match {}", pattern);
+        page_failed();
+    } else {
+        open_queue(queue, &default_file_path()?, options)?;
+    }
+
+    Ok(())
+}
+
+// A page waiting in the queue to be opened along with others.
+enum QueuedPage {
+    // A page name to open in its lowest section.
+    Page(String),
+    // A page name and the section to open it in.
+    Section(String, String),
+    // A page file that has already been found.
+    Path(String),
+}
+
+// The user's choice at the prompt between queued pages.
+enum QueueAction {
+    Continue,
+    Skip,
+    Quit,
+}
+
+// Open queued pages one at a time. When stdout is a terminal the user is prompted between pages
+// to continue to the next page, skip it or quit. Skipping a page prompts again for the one after it.
+// Returns false if the user quit before the end of the queue.
+fn open_queue(queue: Vec<QueuedPage>, default_path: &str, options: &Options) -> BoxResult<bool> {
+    let mut queue = queue.into_iter().peekable();
+
+    while let Some(queued) = queue.next() {
+        let opened = match queued {
+            QueuedPage::Page(page) => first_section(page, options),
+            QueuedPage::Section(page, section) => run_section(&page, &section, default_path, options),
+            QueuedPage::Path(file_path) => run(file_path, options),
+        };
+        // A page that couldn't be opened has already been reported, so the rest are still opened as with the man command.
+        if let Err(e) = opened {
+            if e.downcast_ref::<ReportedError>().is_none() {
+                return Err(e);
+            }
+        }
+
+        while io::stdout().is_terminal() {
+            let next_name = match queue.peek() {
+                Some(QueuedPage::Page(page)) => page.to_string(),
+                Some(QueuedPage::Section(page, section)) => format!("{}({})", page, section),
+                Some(QueuedPage::Path(file_path)) => file_path.split('/').next_back().unwrap_or(file_path).to_string(),
+                None => break,
+            };
+            match queue_prompt(&next_name)? {
+                QueueAction::Continue => break,
+                QueueAction::Skip => {
+                    queue.next();
+                },
+                QueueAction::Quit => return Ok(false),
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+// Ask whether to continue to the next page, skip it or quit, reading a single keypress.
+// Enter or c continues, s skips and q, Esc or Ctrl-C quits.
+fn queue_prompt(next_name: &str) -> BoxResult<QueueAction> {
+    print!("--Next page: {}-- [Enter/c] continue, [s] skip, [q] quit: ", next_name);
+    io::stdout().flush()?;
+
+    ratatui::crossterm::terminal::enable_raw_mode()?;
+    let action = loop {
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        };
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Ok(QueueAction::Quit),
+            KeyCode::Enter | KeyCode::Char('c') => break Ok(QueueAction::Continue),
+            KeyCode::Char('s') => break Ok(QueueAction::Skip),
+            KeyCode::Char('q') | KeyCode::Esc => break Ok(QueueAction::Quit),
+            _ => continue,
+        }
+    };
+    ratatui::crossterm::terminal::disable_raw_mode()?;
+    println!();
+
+    Ok(action?)
+}
+
+// Look up the file paths of a page in the index cache without printing anything or running other programs, for use by external tools.
+// They are in section order as for opening pages, or in the order of the given section (and its extended sections) if there is one.
+// An index file that can't be read is returned as an error instead of being rebuilt.
+pub fn resolve_page(page: &str, section: Option<&str>) -> BoxResult<Vec<PathBuf>> {
+    let files = with_extra_index(read_index_file()?);
+    let sections: Vec<String> = section.map(|section| normalize_section(section).to_lowercase()).into_iter().collect();
+
+    Ok(matching_paths(page, &sections, &files)?.into_iter().map(PathBuf::from).collect())
+}
+
+// Get the file paths of a page from the loaded index cache, from every section when no sections are listed.
+fn matching_paths(page: &str, sections: &[String], files: &HashMap<u32, Cache>) -> BoxResult<Vec<String>> {
+    let entries = page_sections(page, files)?;
+    if sections.is_empty() {
+        return Ok(entries.into_iter().map(|(_, file_path)| file_path).collect());
+    }
+
+    Ok(section_list_paths(&entries, sections))
+}
+
+// Get the file paths of a page in a list of sections, in the order the sections are listed.
+// A section also matches its extended sections, so "3" includes pages in "3p" or "3ssl".
+fn section_list_paths(entries: &[(String, String)], sections: &[String]) -> Vec<String> {
+    let mut results: Vec<String> = Vec::new();
+
+    for section in sections {
+        for (entry_section, file_path) in entries.iter() {
+            if entry_section.to_lowercase().starts_with(section) && !results.contains(file_path) {
+                results.push(file_path.to_string());
+            }
+        }
+    }
+
+    results
+}
+
+// Get the file paths of every section of a page from the index cache, sorted by section order.
+fn page_paths(page: &str) -> BoxResult<Vec<String>> {
+    matching_paths(page, &[], &deserialise_index()?)
+}
+
+// Get the section and file path of every section of a page from the loaded index cache, sorted by section order.
+fn page_sections(page: &str, files: &HashMap<u32, Cache>) -> BoxResult<Vec<(String, String)>> {
+    let roots = man_roots()?;
+    let languages = preferred_languages();
+    let order = section_order();
+    let mut results: Vec<(String, usize, usize, String)> = Vec::new();
+
+    // Match page arg with page in the index cache and pass its section, language rank, directory rank and file path to the Vector.
+    // Translated pages are only included when their language is one of the user's preferred languages. Names are matched ignoring case.
+    for (_, cache) in files.iter() {
+        if cache.page.to_lowercase() == page.to_lowercase() {
+            let (root_rank, root) = page_root(&cache.file_path, &roots);
+            if let Some(rank) = language_rank(&cache.file_path, root, &languages) {
+                results.push((cache.section.to_string(), rank, root_rank, cache.file_path.to_string()));
+            }
+        }
+    }
+
+    // Sort different section numbers by the section order and then in ascending order, preferring languages in priority order
+    // within each section and then the manual directories in order of precedence.
+    results.sort_by(|a, b| section_rank(&a.0, &order).cmp(&section_rank(&b.0, &order))
+        .then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase()))
+        .then_with(|| a.1.cmp(&b.1))
+        .then_with(|| a.2.cmp(&b.2))
+        .then_with(|| a.3.to_lowercase().cmp(&b.3.to_lowercase())));
+
+    Ok(results.into_iter().map(|(section, _, _, file_path)| (section, file_path)).collect())
+}
+
+// Print statistics about the troff source of the lowest section of a page instead of opening it.
+fn page_stats(page: String) -> BoxResult<()> {
+    let results = page_paths(&page)?;
+    let file_path = match results.first() {
+        Some(file_path) => file_path.to_string(),
+        None => {
+            println!("No manual entry for {}", page);
+            page_failed();
+            return Ok(());
+        },
+    };
+
+    let contents = decompress(file_path.clone(), ErrorAction::Fail)?;
+    // A page in an archive is sized by its member, as it has no file of its own. The size is left out if it can't be read.
+    let compressed_size = match archive_member_path(&file_path) {
+        Some((archive, member_path)) => read_archive_member(archive, member_path).map(|member| member.len() as u64),
+        None => fs::metadata(&file_path).map(|metadata| metadata.len()),
+    };
+    let compressed_size = match compressed_size {
+        Ok(size) => format!(" ({} compressed)", size),
+        Err(e) => {
+            error!("Error reading the size of {}: {}", file_path, e);
+            String::new()
+        },
+    };
+
+    // Count the macros that start headings, tables and equations.
+    let count_macro = |names: &[&str]| contents.lines().filter(|line| {
+        let name = line.trim_start_matches(['.', '\'']).split_whitespace().next().unwrap_or("");
+        (line.starts_with('.') || line.starts_with('\'')) && names.contains(&name)
+    }).count();
+    let sections = count_macro(&["SH", "Sh"]);
+    let subsections = count_macro(&["SS", "Ss"]);
+    let tables = count_macro(&["TS"]);
+    let equations = count_macro(&["EQ"]);
+
+    println!("File:        {}", file_path);
+    println!("Lines:       {}", contents.lines().count());
+    println!("Bytes:       {}{}", contents.len(), compressed_size);
+    println!("Sections:    {}", sections);
+    println!("Subsections: {}", subsections);
+    println!("Tables:      {}", if tables > 0 { format!("yes ({})", tables) } else { "no".to_string() });
+    println!("Equations:   {}", if equations > 0 { format!("yes ({})", equations) } else { "no".to_string() });
+
+    Ok(())
+}
+
+// The details of a page printed by the json page command.
+#[derive(serde::Serialize)]
+struct PageJson {
+    name: String,
+    section: String,
+    description: String,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+// Print the details of the lowest section of a page as a JSON object, with its rendered plain text body unless the no body option is set.
+fn json_page(page: String, options: &Options) -> BoxResult<()> {
+    let file_path = page_paths(&page)?.into_iter().next().ok_or(format!("No manual entry for {}", page))?;
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let cache = index.into_values().find(|cache| cache.file_path == file_path).ok_or(format!("No manual entry for {}", page))?;
+
+    let body = if options.no_body {
+        None
+    } else {
+        let contents = decompress(file_path.clone(), ErrorAction::Fail)?;
+        Some(plain_text(&String::from_utf8_lossy(&render_contents(&contents)?)))
+    };
+
+    let page_json = PageJson {
+        name: cache.page,
+        section: cache.section,
+        description: cache.description,
+        path: file_path,
+        body,
+    };
+    println!("{}", serde_json::to_string(&page_json)?);
+
+    Ok(())
+}
+
+// Print the table of contents of the lowest section of a page, listing its section headings with subsection headings indented beneath them.
+fn page_toc(page: String) -> BoxResult<()> {
+    let file_path = match page_paths(&page)?.first() {
+        Some(file_path) => file_path.to_string(),
+        None => {
+            println!("No manual entry for {}", page);
+            page_failed();
+            return Ok(());
+        },
+    };
+
+    let contents = decompress(file_path, ErrorAction::Fail)?;
+    let mut lines = contents.lines();
+
+    while let Some(line) = lines.next() {
+        let request = match line.strip_prefix('.').or_else(|| line.strip_prefix('\'')) {
+            Some(request) => request.trim_start(),
+            None => continue,
+        };
+        let (name, rest) = request.split_once(char::is_whitespace).unwrap_or((request, ""));
+        let indent = match name {
+            "SH" | "Sh" => "",
+            "SS" | "Ss" => "  ",
+            _ => continue,
+        };
+
+        // A heading macro without arguments takes its text from the next line.
+        let mut heading = unescape_troff(&macro_arguments(rest).join(" "));
+        if heading.is_empty() {
+            heading = unescape_troff(lines.next().unwrap_or("").trim());
+        }
+        println!("{}{}", indent, heading);
+    }
+
+    Ok(())
+}
+
+// Print the conventional meaning of a section number and how many pages are indexed in it, including extended sections such as "3ssl".
+fn section_info(section: &str) -> BoxResult<()> {
+    let description = match section.parse::<usize>() {
+        Ok(number) if (1..=9).contains(&number) => SECTION_DESCRIPTIONS[number - 1],
+        _ => {
+            println!("No section {}\nSections are numbered 1-9.", section);
+            return Ok(());
+        },
+    };
+
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let count = index.values().filter(|cache| cache.section.starts_with(section)).count();
+
+    println!("Section {}: {}", section, description);
+    println!("Indexed pages: {}", count);
+
+    Ok(())
+}
+
+// Search the contents and troff/markdown formatting of a file and get the description, along with the names listed in its NAME section.
+fn get_description(path: String) -> BoxResult<(String, Vec<String>)> {
+    // Stubs take their description from the page they redirect to, but not its names, which are indexed with that page.
+    let (path, redirected) = match follow_so_redirects(path.clone()) {
+        Ok(target) => (target.clone(), target != path),
+        Err(e) => {
+            error!("{}", e);
+            (path, false)
+        },
+    };
+    let contents = match decompress(path.clone(), ErrorAction::Log) {
+        Ok(contents) => contents,
+        // Pages that can't be extracted are still indexed without a description, so opening them reports the problem.
+        Err(_) if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > 0) => String::new(),
+        Err(e) => return Err(e),
+    };
+    let names = if redirected { Vec::new() } else { names_from_contents(&contents) };
+
+    Ok((description_from_contents(&contents), names))
+}
+
+// Get the names listed before the dash on the first line of a page's NAME section, such as gzip, gunzip and zcat
+// from "gzip, gunzip, zcat \- compress or expand files", or in the .Nm lines of a page using mdoc macros.
+fn names_from_contents(contents: &str) -> Vec<String> {
+    if let Some((names, _)) = mdoc_name_section(contents) {
+        return names;
+    }
+
+    let mut lines = contents.lines()
+        .skip_while(|line| !line.to_lowercase().starts_with(".sh name") && !line.to_lowercase().starts_with(".sh \"name\""))
+        .skip(1);
+    let line = match lines.find(|line| !line.starts_with('.') && !line.starts_with('\'')) {
+        Some(line) => line,
+        None => return Vec::new(),
+    };
+    let names = match line.split_once("\\-").or_else(|| line.split_once(" - ")) {
+        Some((names, _)) => names,
+        None => return Vec::new(),
+    };
+
+    unescape_troff(names).split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '/'))
+        .collect()
+}
+
+// Get the names and description from the NAME section of a BSD mdoc page, which lists each name with .Nm
+// followed by the description with .Nd (ie: ".Nm gzip ," ".Nm gunzip" ".Nd compress or expand files").
+// Returns None for pages that don't use mdoc macros in their NAME section.
+fn mdoc_name_section(contents: &str) -> Option<(Vec<String>, String)> {
+    let mut lines = contents.lines()
+        .map(|line| line.trim())
+        .skip_while(|line| !(line.starts_with(".Sh ") && line[4..].trim().trim_matches('"').eq_ignore_ascii_case("name")))
+        .skip(1)
+        .take_while(|line| !line.starts_with(".Sh "));
+    let mut names: Vec<String> = Vec::new();
+
+    while let Some(line) = lines.next() {
+        if let Some(arguments) = line.strip_prefix(".Nm ") {
+            // Punctuation such as the commas between names is passed to .Nm as separate arguments.
+            names.extend(arguments.split_whitespace()
+                .filter(|argument| argument.chars().any(|c| c.is_alphanumeric()))
+                .map(|argument| unescape_troff(argument)));
+        } else if let Some(description) = line.strip_prefix(".Nd ") {
+            return Some((names, description.to_string()));
+        }
+    }
+
+    None
+}
+
+// Follow .so redirects from a stub page (ie: one containing just ".so man1/other.1") to the page it sources,
+// returning the path of the final page. Pages that aren't stubs are returned unchanged.
+fn follow_so_redirects(path: String) -> BoxResult<String> {
+    let mut path = path;
+
+    for _ in 0..MAX_SO_REDIRECTS {
+        let target = match so_target(&path) {
+            Some(target) => target,
+            None => return Ok(path),
+        };
+        path = resolve_so_target(&path, &target)
+            .ok_or(format!("manr: {} redirects to {}, which was not found", path, target))?;
+    }
+
+    Err(format!("manr: too many .so redirects from {}", path).into())
+}
+
+// Get the file a page sources with .so when that is the only request in it, ignoring comments and blank lines.
+// Only small page files are read, as stubs are a line long.
+fn so_target(path: &str) -> Option<String> {
+    let length = fs::metadata(path).ok()?.len();
+    if length == 0 || length > MAX_SO_STUB_SIZE {
+        return None;
+    }
+
+    let contents = decompress(path.to_string(), ErrorAction::Log).ok()?;
+    let mut lines = contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with(".\\\"") && !line.starts_with("'\\\"") && *line != ".");
+    let (request, target) = lines.next()?.split_once(char::is_whitespace)?;
+
+    if request == ".so" && !target.trim().is_empty() && lines.next().is_none() {
+        Some(target.trim().to_string())
+    } else {
+        None
+    }
+}
+
+// Find the file for a .so target, which is relative to the manual directory above the stub's section directory
+// (ie: "man1/other.1" from /usr/share/man/man1/name.1.gz). The target may be stored compressed with any page extension.
+fn resolve_so_target(path: &str, target: &str) -> Option<String> {
+    let base = if target.starts_with('/') {
+        PathBuf::from(rebase_root(target))
+    } else {
+        Path::new(path).parent()?.parent()?.join(target)
+    };
+    let base = base.to_string_lossy().to_string();
+
+    std::iter::once(base.clone())
+        .chain(page_extensions().iter().map(|extension| format!("{}.{}", base, extension)))
+        .find(|candidate| Path::new(candidate).is_file())
+}
+
+// Get the description from the NAME section of extracted page contents.
+fn description_from_contents(contents: &str) -> String {
+    if let Some((_, description)) = mdoc_name_section(contents) {
+        return clean_description(&description);
+    }
+
+    let mut description = String::new();
+    let mut lines: Vec<&str> = Vec::new();
+
+    // Push each line of a file's contents into a Vector.
+    for line in contents.lines() {
+        lines.push(line);
+    }
+
+    // Turn the Vector into a iterator.
+    let mut iter = lines.iter();
+    // An additional check to break out of a loop after a pattern is found once.
+    let mut found: bool = false;
+
+    // Iterate over the Vector's lines while they exist or until they match a pattern.
+    while let Some(line) = iter.next() {
+        // If line contains the relevant troff/markdown formatting then get the description from the next lines.
+        if line.to_lowercase().contains(".sh name") || line.to_lowercase().contains(".sh \"name\"") {
+            while let Some(next_lines) = iter.next() {
+                // Check if the next lines contain or end with additional formatting cointaining .nd.
+                if next_lines.to_lowercase().contains(".nd") {
+                    // If the trimmed line ends with additional formatting then skip it and get description from the following line.
+                    if next_lines.trim_end().ends_with(".nd") {
+                        if let Some(following_line) = iter.next() {
+                            description.push_str(following_line);
+                            found = true;
+                            break;
+                        }
+                    // Else if the next lines don't end with .nd remove the ".nd " formatting and get the description from that line.
+                    // The formatting is found ignoring case, while the description keeps the case it was written in.
+                    } else {
+                        let start = next_lines.to_ascii_lowercase().find(".nd").unwrap();
+                        let rest = &next_lines[start + 3..];
+                        let text = format!("{}{}", &next_lines[..start], rest.strip_prefix(' ').unwrap_or(rest));
+                        description.push_str(&text);
+                        found = true;
+                        break;
+                    }
+                // Else check if the next lines contain "-" and after trimming, if ends with additional "-" or "- \\" formatting. 
+                // Then get description.
+                } else {
+                    if next_lines.contains("-") {
+                        if next_lines.trim_end().ends_with("-")  || next_lines.trim_end().ends_with("- \\") {
+                            if let Some(following_line) = iter.next() {
+                                description.push_str(following_line);
+                                found = true;    
+                                break; 
+                            }
+                        // Else if next lines don't end with "-" then split on that line if it has "- " formatting to get description.              
+                        } else {
+                            if let Some(text) = Some(&next_lines.split("- ").last().unwrap().to_string()) {
+                                description.push_str(&text);
+                                found = true;
+                                break;
+                            }
+
+                        }
+                    }
+                }
+            }
+        }
+        if found {
+            break;
+        }
+    }
+
+    clean_description(&description)
+}
+
+// Make an extracted description readable in search results by converting troff escapes, removing control characters
+// and extra whitespace, and truncating very long descriptions.
+fn clean_description(description: &str) -> String {
+    let text: String = unescape_troff(description).chars().filter(|c| !c.is_control()).collect();
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    if text.chars().count() > MAX_DESCRIPTION_LENGTH {
+        let truncated: String = text.chars().take(MAX_DESCRIPTION_LENGTH - 3).collect();
+        format!("{}...", truncated.trim_end())
+    } else {
+        text
+    }
+}
+
+// An index cache struct for entry values to be stored in a related HashMap.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Cache {
+    pub id: u32,
+    pub page: String,
+    pub section: String,
+    pub description: String,
+    pub file_path: String,
+    // The page file's modification time when it was indexed, in seconds since the Unix epoch.
+    pub mtime: u64,
+}
+
+// Create an index cache HashMap for faster searching of manual pages and short descriptions. Automatically runs if empty.
+// Can be updated on demand by using the makewhatis command or could be auto run periodically using a cron job.
+// With a section only the pages in that section are indexed again, replacing their entries in the existing index.
+// Only files added or changed since the last run are read for their descriptions, while entries of removed files are dropped.
+// Without report the summary of the update isn't printed, for updates made automatically ahead of another command's output.
+fn index_cache(section: Option<&str>, report: bool) -> BoxResult<std::io::Result<()>> {
+    // A sorted map is serialised so the same manual pages always produce the same index bytes.
+    let mut index = BTreeMap::new();
+    let roots = man_roots()?;
+
+    // The build time is taken before the directories are read, so pages added while indexing make the index stale.
+    // Updating one section leaves the others as they were, so the previous build time is kept.
+    let build_time = match section {
+        Some(_) => index_build_time().unwrap_or(0),
+        None => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |duration| duration.as_secs()),
+    };
+    let mut results: Vec<(usize, Vec<Cache>)> = Vec::new();
+    let mut entries: Vec<Cache> = Vec::new();
+
+    // Keep the saved entries of other sections when only one section is being updated.
+    // The saved entries being indexed again are kept by path, to be reused for files that haven't changed.
+    // A file has several entries when its NAME section lists several names.
+    let mut previous: HashMap<String, Vec<Cache>> = HashMap::new();
+    for cache in read_index_file().map(|index| index.into_values().collect()).unwrap_or_else(|_| Vec::new()) {
+        if section.is_some_and(|section| !cache.section.to_lowercase().starts_with(section)) {
+            entries.push(cache);
+        } else {
+            previous.entry(cache.file_path.clone()).or_default().push(cache);
+        }
+    }
+    let kept = entries.len();
+
+    let settings = index_settings();
+    let settings = &settings;
+    let overrides = description_overrides();
+    let previous = &previous;
+    let progress = IndexProgress::default();
+    let progress = &progress;
+
+    // Index each manual directory concurrently, then merge the results in the configured order of the directories.
+    let root_results: Vec<Result<Vec<String>, String>> = roots.par_iter()
+        .map(|root| index_root(root, settings, Some(progress), section, previous))
+        .collect();
+    clear_index_progress();
+
+    // The id is assigned once all entries are sorted.
+    for (rank, (root, root_result)) in roots.iter().zip(root_results).enumerate() {
+        match root_result {
+            Ok(root_entries) => results.extend(root_entries.iter()
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| (rank, parse_index_entry(entry, &overrides)))),
+            Err(e) => error!("Error indexing {}: {}", root, e),
+        }
+    }
+
+    // The same page file can be reached under several paths, such as through a symlinked directory or a link giving it another name
+    // (ie: gunzip.1.gz linking to gzip.1.gz), so only one entry is kept for each file, with the names from every path.
+    // Its real path is preferred, then the earliest manual directory and then the first path alphabetically.
+    let real_paths: Vec<PathBuf> = results.iter().map(|(_, caches)| real_file_path(&caches[0].file_path)).collect();
+    let mut preferred: HashMap<&PathBuf, (bool, usize, &str)> = HashMap::new();
+    let mut names: HashMap<&PathBuf, Vec<&str>> = HashMap::new();
+    for ((rank, caches), real_path) in results.iter().zip(real_paths.iter()) {
+        let file_path = caches[0].file_path.as_str();
+        let choice = (Path::new(file_path) != real_path, *rank, file_path);
+        let best = preferred.entry(real_path).or_insert(choice);
+        if choice < *best {
+            *best = choice;
+        }
+        let file_names = names.entry(real_path).or_default();
+        for cache in caches.iter() {
+            if !file_names.contains(&cache.page.as_str()) {
+                file_names.push(&cache.page);
+            }
+        }
+    }
+    let results: Vec<Vec<Cache>> = results.iter().zip(real_paths.iter())
+        .filter_map(|((_, caches), real_path)| {
+            if preferred[real_path].2 != caches[0].file_path {
+                debug!("Skipping {}, which is indexed under another path", caches[0].file_path);
+                return None;
+            }
+            Some(names[real_path].iter().map(|name| Cache { page: name.to_string(), ..caches[0].clone() }).collect())
+        })
+        .collect();
+
+    let (mut added, mut updated) = (0, 0);
+    let mut indexed: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for caches in results {
+        match previous.get(&caches[0].file_path) {
+            None => added += 1,
+            Some(saved) if saved[0].mtime != caches[0].mtime => updated += 1,
+            Some(_) => (),
+        }
+        indexed.insert(caches[0].file_path.clone());
+        entries.extend(caches);
+    }
+    let removed = previous.keys().filter(|file_path| !indexed.contains(*file_path)).count();
+
+    // Directory walking order isn't stable across runs or filesystems, so sort the entries before numbering them from one.
+    entries.sort_by(|a, b| a.page.cmp(&b.page)
+        .then_with(|| a.section.cmp(&b.section))
+        .then_with(|| a.file_path.cmp(&b.file_path)));
+    for (counter, mut index_details) in (1..).zip(entries) {
+        index_details.id = counter;
+        // Insert index struct values into the map.
+        index.insert(counter, index_details);
+    }
+
+    // Serialise the index cache into a bin file.
+    fs::create_dir_all(cache_dir())?;
+    let bin_file = File::create(index_file())?;
+    let mut buffer = BufWriter::new(bin_file);
+    buffer.write_all(&INDEX_MAGIC)?;
+    buffer.write_all(&[INDEX_VERSION])?;
+    buffer.write_all(&build_time.to_le_bytes())?;
+    match bincode2::serialize_into(&mut buffer, &index) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+    }?;
+
+    // Notify user that database was successfully updated.
+    if report {
+        match section {
+            Some(section) => println!("Successfully updated {} manual entries in section {} in database.", index.len() - kept, section),
+            None => println!("Successfully updated manual entries in database."),
+        }
+        println!("{} added, {} updated, {} removed.", added, updated, removed);
+    }
+       
+    Ok(Ok(()))
+}
+
+// Get the real path of a page file with any symbolic links resolved, to find the same page reached under several paths.
+// Pages in archives and files that can't be resolved keep their path.
+fn real_file_path(file_path: &str) -> PathBuf {
+    match archive_member_path(file_path) {
+        Some(_) => PathBuf::from(file_path),
+        None => fs::canonicalize(file_path).unwrap_or_else(|_| PathBuf::from(file_path)),
+    }
+}
+
+// Split an index entry (ie: name (1) - description text /path/name.1.gz) into its index cache details, without an id.
+// Entries for pages with several names (ie: gzip,gunzip,zcat (1) - ...) are split into one for each name, sharing the file.
+fn parse_index_entry(entry: &str, overrides: &HashMap<String, String>) -> Vec<Cache> {
+    // Populate index cache struct with split values.
+    let index_details = Cache {
+        id: 0,
+        page: String::new(),
+        section: entry.split_whitespace().nth(1).map(|s| s.trim_matches(|c| c == '(' || c == ')')).unwrap_or("").to_owned(),
+        description: entry.split_once(" /").unwrap().0.split(" - ").last().unwrap_or("").to_owned(),
+        file_path: entry.split_whitespace().next_back().unwrap_or("").to_owned(),
+        mtime: 0,
+    };
+    let mtime = file_mtime(&index_details.file_path);
+
+    entry.split_whitespace().nth(0).unwrap_or("#").split(',').map(|page| {
+        let mut index_details = Cache { page: page.to_owned(), mtime, ..index_details.clone() };
+
+        // Replace the extracted description with the user's own if one is set for the page and section.
+        if let Some(description) = overrides.get(&format!("{}.{}", index_details.page, index_details.section).to_lowercase()) {
+            index_details.description = description.to_string();
+        }
+
+        index_details
+    }).collect()
+}
+
+// Config settings that control how pages are indexed.
+struct IndexSettings {
+    // Reuse descriptions from a whatis database already in each manual directory.
+    use_existing_whatis: bool,
+    // Prefer the original page name stored in gzip headers over the filename.
+    use_gzip_header_names: bool,
+    // Remove version numbers from page names installed like "name-1.2.1.gz".
+    strip_version_suffixes: bool,
+    // Follow symbolic links while walking manual directories.
+    follow_symlinks: bool,
+    // Read the pages of a directory on all cores rather than one at a time.
+    parallel_indexing: bool,
+}
+
+// Read the indexing settings from the config file, using defaults for any that aren't set.
+fn index_settings() -> IndexSettings {
+    let setting = |key: &str, default: bool| config_setting(key).and_then(|value| value.as_bool()).unwrap_or(default);
+
+    IndexSettings {
+        use_existing_whatis: setting("use_existing_whatis", false),
+        use_gzip_header_names: setting("use_gzip_header_names", false),
+        strip_version_suffixes: setting("strip_version_suffixes", true),
+        follow_symlinks: setting("follow_symlinks", true),
+        parallel_indexing: setting("parallel_indexing", true),
+    }
+}
+
+// The number of pages found and indexed so far across all manual directories being indexed.
+// A running count is shown in place on stderr, unless stderr isn't a terminal so logs aren't filled with it.
+#[derive(Default)]
+struct IndexProgress {
+    total: std::sync::atomic::AtomicUsize,
+    indexed: std::sync::atomic::AtomicUsize,
+}
+
+// Count a page as indexed and update the running count.
+fn count_indexed_page(progress: &IndexProgress) {
+    let count = progress.indexed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+    if io::stderr().is_terminal() {
+        eprint!("\r\x1b[KIndexed {}/{}", count, progress.total.load(std::sync::atomic::Ordering::Relaxed));
+    }
+}
+
+// Clear the running count of indexed pages so other output starts on an empty line.
+fn clear_index_progress() {
+    if io::stderr().is_terminal() {
+        eprint!("\r\x1b[K");
+    }
+}
+
+// Print a line about the progress of indexing on stderr in place of the running count, unless stderr isn't a terminal,
+// so the progress of indexing never mixes with the output of the command that triggered it.
+fn index_progress_line(line: &str) {
+    if io::stderr().is_terminal() {
+        eprintln!("\r\x1b[K{}", line);
+    }
+}
+
+// Index all pages in a single manual directory, reporting progress for the directory as it goes.
+// Returns entries containing all index details concatenated (ie: name (1) - description text /path/name.1.gz).
+// With a section only the pages in that section, including extended sections such as "3ssl" for "3", are indexed.
+// Previously indexed entries, keyed by path, are reused for files not modified since then.
+fn index_root(root: &str, settings: &IndexSettings, progress: Option<&IndexProgress>, section: Option<&str>, previous: &HashMap<String, Vec<Cache>>) -> Result<Vec<String>, String> {
+    let show_progress = progress.is_some();
+    if is_archive(root) {
+        // The members of an archive share its modification time, so they are all reused while the archive is unchanged.
+        let archive_entries: Vec<&Vec<Cache>> = previous.values()
+            .filter(|caches| archive_member_path(&caches[0].file_path).is_some_and(|(archive, _)| archive == root))
+            .collect();
+        if !archive_entries.is_empty() && archive_entries.iter().all(|caches| caches[0].mtime == file_mtime(root)) {
+            return Ok(archive_entries.into_iter().map(|caches| index_entry(caches)).collect());
+        }
+        return index_archive(root, settings, show_progress, section).map_err(|e| e.to_string());
+    }
+
+    let all_files: Vec<DirEntry> = list_all_sections(root, settings.follow_symlinks).map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|file| section.is_none_or(|section| page_and_section(&file.path().to_string_lossy()).1.to_lowercase().starts_with(section)))
+        .collect();
+
+    let whatis = if settings.use_existing_whatis {
+        read_whatis_database(root)
+    } else {
+        HashMap::new()
+    };
+
+    if let Some(progress) = progress {
+        progress.total.fetch_add(all_files.len(), std::sync::atomic::Ordering::Relaxed);
+        index_progress_line(&format!("Indexing {}: {} pages...", root, all_files.len()));
+    }
+
+    // Get an entry containing all index details concatenated for a file.
+    let index_page = |file: &DirEntry| -> Option<String> {
+        if let Some(progress) = progress {
+            count_indexed_page(progress);
+        }
+        let file_path = file.path().to_str().unwrap();
+        if let Some(caches) = previous.get(file_path) {
+            if caches[0].mtime == file_mtime(file_path) {
+                return Some(index_entry(caches));
+            }
+        }
+
+        // Skip files that can't be indexed, such as empty page files. The reason has already been logged.
+        let filename_with_desc = format_filename_and_description(file_path.to_owned(), &whatis, settings).ok()?;
+        Some(filename_with_desc + " " + file_path)
+    };
+
+    // Entries are kept in the order of the files either way, and are sorted again before being numbered.
+    let results: Vec<String> = if settings.parallel_indexing {
+        all_files.par_iter().filter_map(index_page).collect()
+    } else {
+        all_files.iter().filter_map(index_page).collect()
+    };
+
+    if progress.is_some() {
+        index_progress_line(&format!("Indexing {}: done ({} entries)", root, results.len()));
+    }
+
+    Ok(results)
+}
+
+// Join the details of the saved index entries of a file back into an entry as returned when indexing (ie: name (1) - description text /path/name.1.gz),
+// with the names of a page with several joined by commas.
+fn index_entry(caches: &[Cache]) -> String {
+    let pages: Vec<&str> = caches.iter().map(|cache| cache.page.as_str()).collect();
+
+    format!("{} ({}) - {} {}", pages.join(","), caches[0].section, caches[0].description, caches[0].file_path)
+}
+
+// Get the modification time of a page file in seconds since the Unix epoch, or 0 if it can't be read.
+// Pages in an archive have the modification time of the archive.
+fn file_mtime(file_path: &str) -> u64 {
+    let path = archive_member_path(file_path).map_or(file_path, |(archive, _)| archive);
+
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// Index the pages in a tar archive in a single pass over its members, which are read one at a time rather than unpacked.
+// Each page's path is the archive path and the member path joined by "!/" (ie: /docs/bundle.tar.gz!/man1/name.1.gz).
+fn index_archive(archive: &str, settings: &IndexSettings, show_progress: bool, section: Option<&str>) -> BoxResult<Vec<String>> {
+    let suffix = page_file_suffix()?;
+    let mut results: Vec<String> = Vec::new();
+
+    if show_progress {
+        index_progress_line(&format!("Indexing {}...", archive));
+    }
+
+    let mut tar = open_archive(archive)?;
+    for member in tar.entries()? {
+        let mut member = member?;
+        if member.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let member_path = member.path()?.to_string_lossy().trim_start_matches("./").to_string();
+        let file_path = format!("{}!/{}", archive, member_path);
+        if !suffix.is_match(&member_path) {
+            continue;
+        }
+
+        let (page, member_section) = page_and_section(&file_path);
+        if section.is_some_and(|section| !member_section.to_lowercase().starts_with(section)) {
+            continue;
+        }
+        let page = if settings.strip_version_suffixes { strip_version_suffix(&page) } else { page };
+
+        // Skip members that can't be extracted, like files that can't be indexed in a directory.
+        let mut compressed = Vec::new();
+        member.read_to_end(&mut compressed)?;
+        let mut contents = String::new();
+        if let Err(e) = decompressing_reader(&compressed, &member_path).read_to_string(&mut contents) {
+            error!("Error extracting gzip file for {} in section {}: {}", page, member_section, e);
+            continue;
+        }
+
+        let pages = page_with_names(page, names_from_contents(&contents));
+        results.push(format!("{} ({}) - {} {}", pages, member_section, description_from_contents(&contents), file_path));
+    }
+
+    if show_progress {
+        index_progress_line(&format!("Indexing {}: done ({} entries)", archive, results.len()));
+    }
+
+    Ok(results)
+}
+
+// Read descriptions from a whatis database file in the manual directory, keyed by page name and section.
+// Each line is formatted like "name (1) - description", with multiple names separated by commas.
+fn read_whatis_database(default_path: &str) -> HashMap<(String, String), String> {
+    let mut descriptions = HashMap::new();
+    let whatis_path = Path::new(default_path).join("whatis");
+
+    let contents = match std::fs::read_to_string(&whatis_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!("Error reading whatis database {}: {}", whatis_path.display(), e);
+            return descriptions;
+        },
+    };
+
+    let line_format = Regex::new(r"^(.+?)\s*\(([^)]+)\)\s+-+\s+(.*)$").unwrap();
+    for line in contents.lines() {
+        if let Some(captures) = line_format.captures(line) {
+            for name in captures[1].split(',') {
+                descriptions.insert((name.trim().to_string(), captures[2].trim().to_string()), captures[3].trim().to_string());
+            }
+        }
+    }
+
+    descriptions
+}
+
+// Deserialise the index bin file, merged with the pages in any extra directories.
+// An index file that can't be read, such as one truncated or saved by an older build, is rebuilt once before giving up.
+// The rebuild is only noted on stderr, as it runs in the middle of a command whose output goes to stdout.
+fn deserialise_index() -> BoxResult<HashMap<u32, Cache>> {
+    let index = match read_index_file() {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("manr: warning: couldn't read the index file {} ({}), rebuilding it", index_file(), e);
+            index_cache(None, false)??;
+            read_index_file()?
+        },
+    };
+
+    Ok(with_extra_index(index))
+}
+
+// Merge in the pages from any extra directories, numbered after the saved entries.
+fn with_extra_index(mut index: HashMap<u32, Cache>) -> HashMap<u32, Cache> {
+    let mut counter = index.keys().max().copied().unwrap_or(0);
+    for cache in extra_index() {
+        counter += 1;
+        index.insert(counter, Cache { id: counter, ..cache.clone() });
+    }
+
+    index
+}
+
+// Read the entries saved in the index.bin file, without any from extra directories.
+fn read_index_file() -> BoxResult<HashMap<u32, Cache>> {
+    let file = File::open(index_file())?;
+    let mut buffer = BufReader::new(file);
+
+    read_index_header(&mut buffer)?;
+    let index: HashMap<u32, Cache> = bincode2::deserialize_from(buffer)?;
 
     Ok(index)
 }
 
-// Search the index filenames for exact whatis matches.
-fn index_whatis_search(search_term: String) -> BoxResult<()> {
+// Check the header at the start of the index.bin file, returning the time the index was built.
+fn read_index_header<R: Read>(buffer: &mut R) -> BoxResult<u64> {
+    let mut header = [0; 13];
+    if buffer.read_exact(&mut header).is_err() || header[..4] != INDEX_MAGIC || header[4] != INDEX_VERSION {
+        return Err("index file is empty or from another version of manr".into());
+    }
+
+    Ok(u64::from_le_bytes(header[5..].try_into()?))
+}
+
+// Get the time the index was built in seconds since the Unix epoch, or None if there is no readable index.
+fn index_build_time() -> Option<u64> {
+    let mut file = File::open(index_file()).ok()?;
+
+    read_index_header(&mut file).ok()
+}
+
+// Check whether the manual directories have changed since the index was built, by comparing the build time against
+// the modification times of each directory and the directories two levels below it (ie: /usr/share/man/man1 and /usr/share/man/fr/man1),
+// which change when pages are added or removed. Archives are compared by their own modification time.
+fn index_is_stale() -> BoxResult<bool> {
+    let build_time = match index_build_time() {
+        Some(build_time) => build_time,
+        None => return Ok(false),
+    };
+
+    let follow_symlinks = index_settings().follow_symlinks;
+    let newest = man_roots()?.iter()
+        .flat_map(|root| WalkDir::new(root).follow_links(follow_symlinks).max_depth(2).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.depth() == 0 || entry.file_type().is_dir())
+            .map(|entry| file_mtime(&entry.path().to_string_lossy()))
+            .collect::<Vec<u64>>())
+        .max()
+        .unwrap_or(0);
+
+    Ok(newest > build_time)
+}
+
+// Update the index at startup if the manual directories have changed since it was built, so new pages aren't silently missed.
+// The stale_index config key can instead be set to "warn" to only suggest running makewhatis, or "ignore" to keep the index purely manual.
+// An index that can't be updated, such as in a read-only cache directory, is still used as it is.
+fn update_stale_index(options: &Options) -> BoxResult<()> {
+    let setting = config_setting("stale_index").and_then(|value| value.as_str().map(|s| s.to_string())).unwrap_or_else(|| "update".to_string());
+    if setting == "ignore" || !index_is_stale()? {
+        return Ok(());
+    }
+
+    match setting.as_str() {
+        "warn" => eprintln!("manr: warning: the manual directories have changed since the index was built, run 'manr makewhatis' to update it"),
+        _ => {
+            if setting != "update" {
+                error!("Unknown stale_index value {} in config file, expected update, warn or ignore", setting);
+            }
+            if !options.quiet {
+                eprintln!("manr: the manual directories have changed since the index was built; updating the index...");
+            }
+            if let Err(e) = index_cache(None, false).and_then(|result| Ok(result?)) {
+                eprintln!("manr: warning: couldn't update the index ({}), using it as it is", e);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// Get the extra manual directories listed in $MANR_EXTRA_PATH (colon separated), such as a project's own man directory.
+// They are searched after the configured directories without being saved in the index.bin file.
+fn extra_roots() -> Vec<String> {
+    let paths = match env::var_os("MANR_EXTRA_PATH") {
+        Some(paths) => paths,
+        None => return Vec::new(),
+    };
+
+    // Index entries are split on the path's leading "/", so relative directories are made absolute.
+    env::split_paths(&paths)
+        .filter(|path| !path.as_os_str().is_empty())
+        .filter_map(|path| match fs::canonicalize(&path) {
+            Ok(root) => Some(root.to_string_lossy().to_string()),
+            Err(e) => {
+                error!("Error reading {}: {}", path.display(), e);
+                None
+            },
+        })
+        .collect()
+}
+
+// Index the extra manual directories in memory the first time they are needed, so lookups don't require a rebuild.
+fn extra_index() -> &'static [Cache] {
+    static EXTRA_INDEX: std::sync::OnceLock<Vec<Cache>> = std::sync::OnceLock::new();
+
+    EXTRA_INDEX.get_or_init(|| {
+        let settings = index_settings();
+        let overrides = description_overrides();
+        let mut entries: Vec<Cache> = Vec::new();
+
+        for root in extra_roots() {
+            match index_root(&root, &settings, None, None, &HashMap::new()) {
+                Ok(results) => entries.extend(results.iter().filter(|entry| !entry.is_empty()).flat_map(|entry| parse_index_entry(entry, &overrides))),
+                Err(e) => error!("Error indexing {}: {}", root, e),
+            }
+        }
+
+        entries.sort_by(|a, b| a.page.cmp(&b.page)
+            .then_with(|| a.section.cmp(&b.section))
+            .then_with(|| a.file_path.cmp(&b.file_path)));
+        entries
+    })
+}
+
+// Get every entry in the index cache as a Vector sorted by page name and then section, for use by external tools.
+pub fn all_pages() -> BoxResult<Vec<Cache>> {
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let mut pages: Vec<Cache> = index.into_values().collect();
+
+    pages.sort_by(|a, b| a.page.cmp(&b.page).then_with(|| a.section.cmp(&b.section)).then_with(|| a.file_path.cmp(&b.file_path)));
+
+    Ok(pages)
+}
+
+// Print statistics about the index cache to help spot pages whose details weren't extracted well.
+fn index_stats() -> BoxResult<()> {
     let index: HashMap<u32, Cache> = deserialise_index()?;
+    let total = index.len();
+    let empty_descriptions = index.values().filter(|cache| cache.description.trim().is_empty()).count();
+    let mut names: Vec<&str> = index.values().map(|cache| cache.page.as_str()).collect();
+    names.sort();
+    names.dedup();
+    let description_length: usize = index.values().map(|cache| cache.description.chars().count()).sum();
+    let average_length = if total > 0 { description_length as f64 / total as f64 } else { 0.0 };
+    let file_size = fs::metadata(index_file())?.len();
+
+    // Group the entries by the leading character of their section, so extended sections such as "3ssl" count towards section 3.
+    let mut sections: BTreeMap<char, usize> = BTreeMap::new();
+    for cache in index.values() {
+        if let Some(section) = cache.section.chars().next() {
+            *sections.entry(section).or_insert(0) += 1;
+        }
+    }
+
+    println!("Entries:                    {}", total);
+    println!("Empty descriptions:         {}", empty_descriptions);
+    println!("Distinct pages:             {}", names.len());
+    println!("Average description length: {:.1}", average_length);
+    println!("Index file size:            {} bytes", file_size);
+    println!("Sections:");
+    for (section, count) in sections {
+        println!("  {:<3} {:>8}", section, count);
+    }
+
+    Ok(())
+}
+
+// List the pages in the manual directories that are new or were modified since a point in time, with their sections.
+// "last" means since each page was indexed, comparing its modification time with the one saved in its index entry,
+// when pages that aren't in the index are also reported as new. Otherwise the time is a duration before now such as "30m", "12h", "7d" or "2w".
+fn changed_since(since: &str) -> BoxResult<()> {
+    let since_last = since == "last";
+    let cutoff = if since_last {
+        None
+    } else {
+        let duration = parse_duration(since).ok_or(format!("manr: invalid time '{}', expected 'last' or a duration such as 7d", since))?;
+        Some(std::time::SystemTime::now().checked_sub(duration).unwrap_or(std::time::UNIX_EPOCH))
+    };
+
+    // A page file reached under several paths, such as through a symlink giving it another name, is indexed once under one of them,
+    // so files are matched to the index by their real path and reported under the indexed path.
+    let indexed: HashMap<PathBuf, (String, u64)> = read_index_file()?.into_values()
+        .map(|cache| (real_file_path(&cache.file_path), (cache.file_path, cache.mtime)))
+        .collect();
+    let follow_symlinks = index_settings().follow_symlinks;
     let mut results: Vec<String> = Vec::new();
 
+    // Archives are left out as their members have no modification time of their own on disk.
+    for root in man_roots()?.iter().filter(|root| !is_archive(root)) {
+        for file in list_all_sections(root, follow_symlinks)? {
+            let path = file.path().to_string_lossy().to_string();
+            let indexed_entry = indexed.get(&real_file_path(&path));
+            let changed = match cutoff {
+                Some(cutoff) => file.metadata().ok().and_then(|metadata| metadata.modified().ok()).is_some_and(|modified| modified > cutoff),
+                None => indexed_entry.is_none_or(|(_, mtime)| *mtime != file_mtime(&path)),
+            };
+
+            if changed {
+                let (page, section) = page_and_section(indexed_entry.map_or(&path, |(indexed_path, _)| indexed_path));
+                results.push(format!("{} ({}) - {}", page, section, if indexed_entry.is_none() { "new" } else { "changed" }));
+            }
+        }
+    }
+
+    results.sort();
+    results.dedup();
+    if results.is_empty() {
+        println!("No manual pages changed since {}", since);
+    }
+    for result in results {
+        println!("{}", result);
+    }
+
+    Ok(())
+}
+
+// Parse a duration made of a number and a unit of m (minutes), h (hours), d (days) or w (weeks), such as "7d".
+fn parse_duration(duration: &str) -> Option<std::time::Duration> {
+    let duration = duration.trim();
+    let unit = duration.chars().next_back()?;
+    let number: u64 = duration[..duration.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(std::time::Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+// Search the index filenames for exact whatis matches.
+fn index_whatis_search(search_term: String, options: &Options) -> BoxResult<()> {
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let mut results: Vec<&Cache> = Vec::new();
+    let search_term = search_term.to_lowercase();
+
+    // Names are compared in lowercase, as pages may be indexed with capitals in their names.
     for (_, cache) in index.iter() {
-        if cache.page == search_term {
-            results.push(format!("{} ({}) - {}", cache.page, cache.section, cache.description));
+        if cache.page.to_lowercase() == search_term && in_sections(cache, options) {
+            results.push(cache);
         }
     }
 
-    display_index_results(results, search_term)?;
+    if options.json {
+        return display_json_results(results);
+    }
+    display_index_results(results.iter().map(|cache| search_result(cache, options)).collect(), search_term, options)?;
 
     Ok(())
 }
 
 // Apropos search index filenames and short descriptions for results containing a search term.
-fn index_apropos_search(search_term: String) -> BoxResult<()> {
+// With the regex option the search term is instead a regular expression to match, ignoring case (ie: manr --regex -k '^gpg').
+fn index_apropos_search(search_term: String, options: &Options) -> BoxResult<()> {
     let index: HashMap<u32, Cache> = deserialise_index()?;
-    let mut results: Vec<String> = Vec::new();
+    let mut results: Vec<&Cache> = Vec::new();
+
+    let pattern = if options.regex {
+        Some(regex::RegexBuilder::new(&search_term).case_insensitive(true).build()
+            .map_err(|e| format!("manr: invalid regular expression '{}': {}", search_term, e))?)
+    } else {
+        None
+    };
+    let search_term = if options.regex { search_term } else { search_term.to_lowercase() };
+
+    // Names and descriptions are compared in lowercase, as they are indexed in their original case.
+    let matches = |text: &str| match pattern.as_ref() {
+        Some(pattern) => pattern.is_match(text),
+        None => text.to_lowercase().contains(&search_term),
+    };
 
     for (_, cache) in index.iter() {
-        if cache.page.contains(&search_term) || cache.description.contains(&search_term) {
-            results.push(format!("{} ({}) - {}", cache.page, cache.section, cache.description));
+        if (matches(&cache.page) || matches(&cache.description)) && in_sections(cache, options) {
+            results.push(cache);
+        }
+    }
+
+    if options.json {
+        return display_json_results(results);
+    }
+    display_index_results(results.iter().map(|cache| search_result(cache, options)).collect(), search_term, options)?;
+
+    Ok(())
+}
+
+// Search the whole text of every indexed page, or only those in the sections option, for a search term ignoring case.
+// The troff requests and escapes are stripped from each page first, so only the text a reader sees is matched, and each page file
+// is searched once however many names it has. Pages are read in parallel with a running count shown on stderr, as every page has to be decompressed. Pages that can't
+// be read are logged and skipped. With the max results option the search stops once that many pages are found.
+fn global_apropos_search(search_term: String, options: &Options) -> BoxResult<()> {
+    let mut pages = all_pages()?;
+    pages.retain(|cache| in_sections(cache, options));
+    let mut searched_paths: std::collections::HashSet<String> = std::collections::HashSet::new();
+    pages.retain(|cache| searched_paths.insert(cache.file_path.clone()));
+
+    let total = pages.len();
+    let searched = std::sync::atomic::AtomicUsize::new(0);
+    let found = std::sync::atomic::AtomicUsize::new(0);
+    let max_results = options.max_results.unwrap_or(usize::MAX);
+    let show_progress = io::stderr().is_terminal();
+
+    let mut results: Vec<String> = pages.par_iter().filter_map(|cache| {
+        if found.load(std::sync::atomic::Ordering::Relaxed) >= max_results {
+            return None;
+        }
+
+        let contents = decompress(cache.file_path.to_string(), ErrorAction::Log).ok();
+        let count = searched.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        if show_progress {
+            eprint!("\rSearched {}/{}", count, total);
+        }
+
+        if strip_troff(&contents?).to_lowercase().contains(&search_term) {
+            found.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Some(format!("{} ({})", cache.page, cache.section))
+        } else {
+            None
+        }
+    }).collect();
+
+    if show_progress {
+        eprintln!();
+    }
+    results.truncate(max_results);
+    display_index_results(results, search_term, options)?;
+
+    Ok(())
+}
+
+// Check whether an indexed page is in one of the sections given with the sections option, including extended sections
+// such as "3ssl" for "3". Every page is included when no sections are given.
+fn in_sections(cache: &Cache, options: &Options) -> bool {
+    options.sections.is_empty() || options.sections.iter().any(|section| cache.section.to_lowercase().starts_with(section))
+}
+
+// List the indexed pages, optionally only those in a section, in the same format as search results.
+// With the names only option just the distinct page names are printed, in columns when stdout is a terminal.
+fn list_pages(section: Option<String>, options: &Options) -> BoxResult<()> {
+    let index: HashMap<u32, Cache> = deserialise_index()?;
+    let matches: Vec<&Cache> = index.values()
+        .filter(|cache| section.as_ref().is_none_or(|section| cache.section.to_lowercase().starts_with(section)))
+        .collect();
+
+    if matches.is_empty() {
+        if let Some(section) = section {
+            println!("No manual entries in section {}", section);
         }
+        return Ok(());
     }
 
-    display_index_results(results, search_term)?;
+    if options.names_only {
+        let mut names: Vec<String> = matches.iter().map(|cache| cache.page.to_string()).collect();
+        names.sort();
+        names.dedup();
+
+        if io::stdout().is_terminal() {
+            print_columns(&names, terminal_width().unwrap_or(80));
+        } else {
+            for name in names {
+                println!("{}", name);
+            }
+        }
+    } else {
+        let results: Vec<String> = matches.iter()
+            .map(|cache| search_result(cache, options))
+            .collect();
+        display_index_results(results, section.unwrap_or_default(), options)?;
+    }
 
     Ok(())
 }
 
+// Print names in columns that fit the terminal width, filling each column top to bottom like ls.
+fn print_columns(names: &[String], width: usize) {
+    let column_width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0) + 2;
+    let columns = (width / column_width).max(1);
+    let rows = names.len().div_ceil(columns);
+
+    for row in 0..rows {
+        let mut line = String::new();
+        for column in 0..columns {
+            if let Some(name) = names.get(column * rows + row) {
+                line.push_str(&format!("{:width$}", name, width = column_width));
+            }
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+// Get the terminal width in columns from the COLUMNS environment variable, or else by asking the terminal,
+// as shells don't usually export COLUMNS.
+fn terminal_width() -> Option<usize> {
+    env::var("COLUMNS").ok().and_then(|width| width.trim().parse::<usize>().ok())
+        .or_else(|| ratatui::crossterm::terminal::size().ok().map(|(columns, _)| columns as usize))
+        .filter(|width| *width > 0)
+}
+
+// Open a full screen list of the indexed pages with a filter box. Typing narrows the list by page name or description,
+// the arrow keys move the selection, Enter opens the selected page and Esc quits. Both stdin and stdout must be terminals.
+fn browse(options: &Options) -> BoxResult<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("manr: --browse needs a terminal".into());
+    }
+
+    let pages = all_pages()?;
+    let mut filter = String::new();
+    let mut state = ListState::default().with_selected(Some(0));
+    let mut terminal = ratatui::try_init()?;
+
+    let result: BoxResult<()> = loop {
+        let search_term = filter.to_lowercase();
+        let matches: Vec<&Cache> = pages.iter()
+            .filter(|cache| cache.page.to_lowercase().contains(&search_term) || cache.description.to_lowercase().contains(&search_term))
+            .collect();
+
+        // Keep the selection within the filtered list.
+        let selected = state.selected().unwrap_or(0).min(matches.len().saturating_sub(1));
+        state.select(Some(selected));
+
+        let draw = terminal.draw(|frame| {
+            let [filter_area, list_area] = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(frame.area());
+            let items: Vec<String> = matches.iter()
+                .map(|cache| format!("{} ({}) - {}", cache.page, cache.section, cache.description))
+                .collect();
+
+            frame.render_widget(Paragraph::new(filter.as_str()).block(Block::bordered().title("Filter (Enter to open, Esc to quit)")), filter_area);
+            frame.render_stateful_widget(
+                List::new(items)
+                    .block(Block::bordered().title(format!("Pages ({})", matches.len())))
+                    .highlight_style(Style::new().reversed())
+                    .highlight_symbol("> "),
+                list_area,
+                &mut state,
+            );
+        });
+        if let Err(e) = draw {
+            break Err(Box::new(e));
+        }
+
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(Box::new(e)),
+        };
+
+        match key.code {
+            KeyCode::Esc => break Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Ok(()),
+            KeyCode::Char(c) => filter.push(c),
+            KeyCode::Backspace => {
+                filter.pop();
+            },
+            KeyCode::Up => state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down => state.select(Some(selected + 1)),
+            KeyCode::PageUp => state.select(Some(selected.saturating_sub(10))),
+            KeyCode::PageDown => state.select(Some(selected + 10)),
+            KeyCode::Enter => {
+                if let Some(cache) = matches.get(selected) {
+                    // Hand the terminal back to groff and the pager while the page is open.
+                    ratatui::restore();
+                    let opened = run(cache.file_path.to_string(), options);
+                    terminal = match ratatui::try_init() {
+                        Ok(terminal) => terminal,
+                        Err(e) => break Err(Box::new(e)),
+                    };
+                    if let Err(e) = opened {
+                        break Err(e);
+                    }
+                }
+            },
+            _ => {},
+        }
+    };
+
+    ratatui::restore();
+
+    result
+}
+
+// Format an index entry as a search result (ie: name (1) - description text).
+// With the verbose option, pages found in a documentation directory from extra_doc_dirs are followed by that directory.
+fn search_result(cache: &Cache, options: &Options) -> String {
+    let result = format!("{} ({}) - {}", cache.page, cache.section, cache.description);
+    if !options.verbose {
+        return result;
+    }
+
+    match extra_doc_dirs().iter().find(|dir| Path::new(&cache.file_path).starts_with(dir)) {
+        Some(dir) => format!("{} [{}]", result, dir),
+        None => result,
+    }
+}
+
 // Sort and display index search results.
-fn display_index_results(mut results: Vec<String>, search_term: String) -> BoxResult<()> {
+fn display_index_results(mut results: Vec<String>, search_term: String, options: &Options) -> BoxResult<()> {
     results.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
     // Remove duplicate consecutive results from the sorted Vector.
     results.dedup();
@@ -522,15 +4368,131 @@ fn display_index_results(mut results: Vec<String>, search_term: String) -> BoxRe
     if results.is_empty() {
         println!("{}: nothing appropriate", search_term);
     } else {
-        for result in results {
+        for result in results.iter() {
             println!("{}", result);
         }
+        if options.breakdown {
+            println!("{}", section_breakdown(&results));
+        }
     }
 
     Ok(())
 }
 
+// A whatis or apropos search result printed by the json option.
+#[derive(serde::Serialize, PartialEq)]
+struct SearchResultJson<'a> {
+    page: &'a str,
+    section: &'a str,
+    description: &'a str,
+}
+
+// Sort index search results as for display_index_results and print them as a JSON array of objects,
+// which is empty rather than a message when nothing matches so the output is always valid JSON.
+fn display_json_results(results: Vec<&Cache>) -> BoxResult<()> {
+    let mut results: Vec<SearchResultJson> = results.iter()
+        .map(|cache| SearchResultJson { page: &cache.page, section: &cache.section, description: &cache.description })
+        .collect();
+    results.sort_by(|a, b| a.page.to_lowercase().cmp(&b.page.to_lowercase())
+        .then_with(|| a.section.to_lowercase().cmp(&b.section.to_lowercase()))
+        .then_with(|| a.description.to_lowercase().cmp(&b.description.to_lowercase())));
+    results.dedup();
+
+    println!("{}", serde_json::to_string(&results)?);
+
+    Ok(())
+}
+
+// Summarise how many results fall in each section, grouped by the leading character of the section (ie: "Sections: 1: 12, 3: 40, 8: 2").
+fn section_breakdown(results: &[String]) -> String {
+    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+
+    for result in results {
+        // Results are formatted as "page (section) - description".
+        let section = result.split_once(" (").and_then(|(_, rest)| rest.chars().next());
+        if let Some(section) = section {
+            *counts.entry(section).or_insert(0) += 1;
+        }
+    }
+
+    let counts: Vec<String> = counts.iter().map(|(section, count)| format!("{}: {}", section, count)).collect();
+    format!("Sections: {}", counts.join(", "))
+}
+
 // A default help message to be displayed. 
 fn help() {
     println!("Try 'manr --help' or 'manr --usage' for more information.");
 }
+
+// Print the short synopsis of the command line.
+fn print_usage() {
+    println!("Usage: manr [OPTION...] [SECTION] PAGE...");
+}
+
+// Print the synopsis followed by the commands, options and example invocations.
+fn print_help() {
+    print_usage();
+    println!("
+Open manual pages, using the lowest section of a page unless a section number (1-9, optionally
+with a suffix such as 3ssl) is given before it.
+
+Commands:
+  -f, --whatis PAGE         show the short descriptions of pages named PAGE
+  -k, --apropos TERM        search page names and descriptions for TERM
+  -K, --global-apropos TERM search the whole text of every page for TERM
+  -l, --local-file FILE     format and display a local page file, or stdin with '-'
+  -w, --where PAGE          print the file paths of a page instead of opening it
+  -a, --all PAGE            open every section of a page in turn
+  makewhatis [SECTION]      rebuild the index, or only the pages in SECTION
+  --list [SECTION]          list the indexed pages
+  --glob PATTERN            open the pages matching a glob pattern
+  --compare FILE FILE       compare the rendered output of two page files
+  --browse                  interactively filter and open indexed pages
+  --export-all              render the indexed pages into files in the output directory
+  --toc PAGE                print the section headings of a page
+  --json-page PAGE          print the details and text of a page as JSON
+  --stats PAGE              print statistics about the troff source of a page
+  --stats-index             print statistics about the index
+  --section-info SECTION    print what a section number means
+  --changed-since WHEN      list pages changed since the last index ('last') or a duration such as 7d
+  --update-if-stale         update the index only if the manual directories changed since it was built
+  --troff-lint              report indexed pages that produce groff warnings or errors
+  --help                    print this help
+  --usage                   print a short usage message
+
+Options:
+  --pager COMMAND           view pages with COMMAND instead of 'less -R'
+  -t, --troff               write pages to stdout as PostScript, for printing
+  --html                    write pages as HTML to stdout, or to a file ending in .html given after the page
+  -s, --sections LIST       search a colon or comma separated list of sections in order, or
+                            limit whatis and apropos results to them
+  --format FORMAT           the output format of pages, such as text, html or ps
+  --output-dir DIR          the directory to write exported pages into
+  --names-only              list only page names
+  --breakdown               count search results in each section
+  --regex                   match apropos search terms as regular expressions
+  --json                    print whatis and apropos results as a JSON array
+  --max-results N           stop a --global-apropos search after N pages are found
+  --encoding-detect         convert pages in legacy encodings before formatting
+  --hyperlinks              make links in pages clickable in supporting terminals
+  --color                   colour the bold and underlined text of pages, such as headings
+  --fuzzy                   open the closest matching page name when a page isn't found
+  --no-index                find pages by searching the manual directories instead of the index
+  --fallback-section        open the lowest section of a page missing from the section given
+  --number                  show line numbers in the pager
+  --show-command            print the groff and pager commands instead of running them
+  --no-body                 leave the page text out of --json-page output
+  --chroot DIR              read pages from a mounted root filesystem
+  --explain                 print the equivalent man command
+  -v, --verbose             show which extra directory search results came from
+  -q, --quiet               build the index on first run without asking
+
+Examples:
+  manr ls                   open the lowest section of ls
+  manr 3 printf             open printf from section 3
+  manr printf.3             open printf from section 3
+  manr -k directory         search for pages about directories
+  manr -k -s 3 alloc        search for pages about allocation in section 3
+  manr --regex -k '^gpg'    search for pages whose names or descriptions start with gpg
+  manr -w printf            print the file path of printf");
+}
@@ -2,7 +2,12 @@ fn main() {
     env_logger::init();
 
     if let Err(e) = manr::get_args() {
-        eprintln!("{}", e);
+        // Errors opening a page are already reported where they happen.
+        if e.downcast_ref::<manr::ReportedError>().is_none() {
+            eprintln!("{}", e);
+        }
         std::process::exit(1);
     }
+
+    std::process::exit(manr::exit_status());
 }
@@ -1,6 +1,6 @@
 use assert_cmd::Command as AssertCommand;
 use predicates::prelude::*;
-use std::{io::prelude::*, io::BufReader, io::Read, error::Error, process::Command as StdCommand, process::Stdio};
+use std::{env, fs, error::Error};
 use manr::*;
 
 type TestResult = Result<(), Box<dyn Error>>;
@@ -16,57 +16,24 @@ const CAT1_GZ: &str = "./tests/inputs/cat.1.gz";
 const CHMOD1_GZ: &str = "./tests/inputs/chmod.1.gz";
 const CHMOD2_GZ: &str = "./tests/inputs/chmod.2.gz";
 const CHROOT8_GZ: &str = "./tests/inputs/chroot.8.gz";
+const CHSH1_FR_GZ: &str = "./tests/inputs/fr/chsh.1.gz";
+const UPCASE_CMD: &str = "upcase";
+const UPCASE1_GZ: &str = "./tests/inputs/MAN1/upcase.1.gz";
+const EMPTY_CMD: &str = "empty";
+const TABLE_CMD: &str = "table";
+const VERSIONED_CMD: &str = "versioned";
+const VERSIONED1_GZ: &str = "./tests/inputs/versioned-1.2.1.gz";
 const PERM_DENIED_CMD: &str = "permdenied";
 const PERM_DENIED_GZ: &str = "./tests/inputs/permdenied.1.gz";
 const BAD_GZ_CMD: &str = "badgzip";
 const BAD_GZ: &str = "./tests/inputs/badgzip.1.gz";
 
-// A test function for the run function which normally extracts, formats and displays manual files.
-// This function instead prints the stdout to a String. 
-// Also possible to instead change the main function's return type to a Child to convert the stdout externally.
-pub fn run_to_string(path: String) -> String {
-    // Extract gzip manual file.
-    let contents = extract_gzip(path, ErrorAction::Fail);
-
-    // Load extracted gzip contents into groff application with UTF-8 formatting. (Seems to have issue formatting numbered/nested lists.)
-    let mut groff = StdCommand::new("groff")
-    .arg("-mandoc")
-    .arg("-Tutf8")
-    .stdin(Stdio::piped())
-    .stdout(Stdio::piped())
-    .spawn()
-    .unwrap();
-
-    {
-        let stdin = groff.stdin.as_mut().unwrap();
-        stdin.write_all(contents.expect("Couldn't write groff stdout").as_bytes()).unwrap();
-    }
-
-    groff.wait().unwrap();
-
-    // Pass groff's formatted document into the less viewer application.
-    let mut less = StdCommand::new("less")
-    .arg("-R")
-    .stdin(groff.stdout.unwrap())
-    .stdout(Stdio::piped()) // Changed from inherit to piped for testing run_to_string purposes.
-    .spawn()
-    .expect("Failed to spawn less process");
-
-    // Convert the less stdout to a String for testing purposes.
-    let stdout = less.stdout.take().unwrap();
-    let mut buffer = BufReader::new(stdout);
-    let mut stdout_str = String::new();
-    buffer.read_to_string(&mut stdout_str).unwrap();
-
-    stdout_str
-}
-
 // Some test results may vary depending on what manuals are stored in default directory.
 
 #[test]
 fn run_and_extract_page_and_open_with_groff_and_less() -> TestResult {
     let page = "man";
-    let expected = run_to_string(MAN1_GZ.to_string());
+    let expected = render_page(MAN1_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
         .args([&page])
@@ -81,9 +48,9 @@ fn run_and_extract_multiple_pages_and_open_sequentially_with_groff_and_less() ->
     let page1 = "man";
     let page2 = "cat";
     let page3 = "chmod";
-    let expected1 = run_to_string(MAN1_GZ.to_string());
-    let expected2 = run_to_string(CAT1_GZ.to_string());
-    let expected3 = run_to_string(CHMOD1_GZ.to_string());
+    let expected1 = render_page(MAN1_GZ)?;
+    let expected2 = render_page(CAT1_GZ)?;
+    let expected3 = render_page(CHMOD1_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
         .args([&page1, &page2, &page3])
@@ -99,7 +66,7 @@ fn run_and_extract_multiple_pages_and_open_sequentially_with_groff_and_less() ->
 fn run_and_extract_page_with_section_and_open_with_groff_and_less() -> TestResult {
     let page = "man";
     let section = "7";
-    let expected = run_to_string(MAN7_GZ.to_string());
+    let expected = render_page(MAN7_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
         .args([&section, &page])
@@ -113,13 +80,13 @@ fn run_and_extract_page_with_section_and_open_with_groff_and_less() -> TestResul
 fn run_and_extract_multiple_pages_with_sections_and_open__sequentially_with_groff_and_less() -> TestResult {
     let page1 = "man";
     let section1 = "7";
-    let expected1 = run_to_string(MAN7_GZ.to_string());
+    let expected1 = render_page(MAN7_GZ)?;
     let page2 = "chmod";
     let section2 = "2";
-    let expected2 = run_to_string(CHMOD2_GZ.to_string());
+    let expected2 = render_page(CHMOD2_GZ)?;
     let page3 = "chroot";
     let section3 = "8";
-    let expected3 = run_to_string(CHROOT8_GZ.to_string());
+    let expected3 = render_page(CHROOT8_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
         .args([&section1, &page1, &section2, &page2, &section3, &page3])
@@ -131,177 +98,1861 @@ fn run_and_extract_multiple_pages_with_sections_and_open__sequentially_with_grof
     Ok(())
 }
 
+// Requires a French translation of chsh in the default search directory, such as fr/man1/chsh.1.gz.
 #[test]
-fn page_not_found() -> TestResult {
-    let bad_page = PAGE_NOT_FOUND;
-    let expected = format!("No manual entry for {}", bad_page);
+fn run_page_in_first_available_preferred_language() -> TestResult {
+    let page = "chsh";
+    let expected = render_page(CHSH1_FR_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
-        .args([&bad_page])
+        .env("LANGUAGE", "xx:fr:de")
+        .env("LANG", "fr_FR.UTF-8")
+        .args([&page])
         .assert()
-        .stdout(predicate::str::is_match(expected)?);
+        .stdout(predicate::str::contains(format!("{}", expected)));
 
     Ok(())
 }
 
 #[test]
-fn page_not_found_when_opening_multiple() -> TestResult {
-    let page1 = "man";
-    let expected1 = run_to_string(MAN1_GZ.to_string());
-    let page2 = "chmod";
-    let expected2 = run_to_string(CHMOD1_GZ.to_string());
-    let page3 = "cat";
-    let expected3 = run_to_string(CAT1_GZ.to_string());
-    let bad_page = PAGE_NOT_FOUND;
-    let expected4 = format!("No manual entry for {}", bad_page);
+fn run_page_with_fuzzy_name() -> TestResult {
+    let expected = render_page(CHMOD1_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
-        .args([&page1, &page2, &page3, &bad_page])
+        .args(["--fuzzy", "chmd"])
         .assert()
-        .stdout(predicate::str::contains(format!("{}", expected1)))
-        .stdout(predicate::str::contains(format!("{}", expected2)))
-        .stdout(predicate::str::contains(format!("{}", expected3)))
-        .stdout(predicate::str::is_match(expected4)?);
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected)));
 
     Ok(())
 }
 
 #[test]
-fn page_section_not_found() -> TestResult {
-    let page = "man";   // Same result for a bad page
-    let bad_sect = SECT_NOT_FOUND;
-    let expected = format!("No manual entry for {} in section {}\n", page, bad_sect);
+fn run_page_with_custom_pager() -> TestResult {
+    let page = "man";
+    let expected = render_page(MAN1_GZ)?;
+
     AssertCommand::cargo_bin(PRG)?
-        .args([&bad_sect, &page])
+        .args(["--pager", "cat", &page])
         .assert()
-        .stdout(predicate::str::is_match(expected)?);
+        .stdout(predicate::str::contains(format!("{}", expected)));
 
     Ok(())
 }
 
 #[test]
-fn page_section_not_found_when_opening_multiple() -> TestResult {
-    let page1 = "man";
-    let section1 = "7";
-    let expected1 = run_to_string(MAN7_GZ.to_string());
-    let page2 = "chmod";
-    let section2 = "2";
-    let expected2 = run_to_string(CHMOD2_GZ.to_string());
-    let page3 = "chroot";
-    let section3 = "8";
-    let expected3 = run_to_string(CHROOT8_GZ.to_string());
-    let page4 = "cat";    
-    let bad_section = SECT_NOT_FOUND;
-    let expected4 = format!("No manual entry for {} in section {}", page4, bad_section);
+fn run_page_as_postscript() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-t", "man"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("%!PS"));
+
+    Ok(())
+}
+
+// Pages can be written as HTML to a file given after the page.
+#[test]
+fn run_page_as_html_file() -> TestResult {
+    let output_file = env::temp_dir().join(format!("manr-test-{}.html", std::process::id()));
 
     AssertCommand::cargo_bin(PRG)?
-        .args([&section1, &page1, &section2, &page2, &section3, &page3, &bad_section, &page4])
+        .args(["--html", "1", "man", output_file.to_str().unwrap()])
         .assert()
-        .stdout(predicate::str::contains(format!("{}", expected1)))
-        .stdout(predicate::str::contains(format!("{}", expected2)))
-        .stdout(predicate::str::contains(format!("{}", expected3)))
-        .stdout(predicate::str::is_match(expected4)?);
+        .success();
+
+    let html = fs::read_to_string(&output_file)?;
+    assert!(html.contains("<html"));
+
+    // Every section opened with the all option is written to the file, replacing what was there before.
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--html", "-a", "man", output_file.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let all_html = fs::read_to_string(&output_file)?;
+    fs::remove_file(&output_file)?;
+    assert_eq!(all_html.matches("<html").count(), 2);
 
     Ok(())
 }
 
+// The pager is skipped when stdout isn't a terminal, so a pager that prints nothing doesn't hide the page.
 #[test]
-fn whatis_search() -> TestResult {
-    let command = "-f";
+fn run_page_piped_without_pager() -> TestResult {
     let page = "man";
-    let expected = vec!["man (1) - an interface to the system reference manuals", "man (7) - macros to format man pages"];  
+    let expected = render_page(MAN1_GZ)?;
+
     AssertCommand::cargo_bin(PRG)?
-        .args([&command, &page])
+        .args(["--pager", "false", &page])
         .assert()
-        .stdout(predicate::str::contains(expected.join("\n")));
-  
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
     Ok(())
 }
 
+// Pagers without a line number option show the page unchanged.
 #[test]
-fn whatis_search_not_found() -> TestResult {
-    let command = "-f";
-    let bad_page = PAGE_NOT_FOUND;
-    let expected = format!("{}: nothing appropriate", bad_page);  
+fn run_page_with_line_numbers_in_other_pager() -> TestResult {
+    let page = "man";
+    let expected = render_page(MAN1_GZ)?;
+
     AssertCommand::cargo_bin(PRG)?
-        .args([&command, &bad_page])
+        .args(["--number", "--pager", "cat", &page])
         .assert()
-        .stdout(predicate::str::is_match(expected)?);
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected)));
 
     Ok(())
 }
 
 #[test]
-fn apropos_search() -> TestResult {
-    let command = "-k";
-    let page = "zcat";
-    let expected = vec!("bzcat (1) - a block-sorting file compressor, v1.0.8",
-    "lzcat (1) - compress or decompress .xz and .lzma files",
-    "xzcat (1) - compress or decompress .xz and .lzma files",
-    "zcat (1) - compress or expand files");
+fn run_all_sections_of_page() -> TestResult {
+    let expected1 = render_page(MAN1_GZ)?;
+    let expected7 = render_page(MAN7_GZ)?;
+
     AssertCommand::cargo_bin(PRG)?
-        .args([&command, &page])
+        .args(["-a", "--pager", "cat", "man"])
         .assert()
-        .stdout(predicate::str::contains(expected.join("\n")));
+        .success()
+        .stdout(predicate::str::contains(format!("{}{}", expected1, expected7)));
 
     Ok(())
 }
 
 #[test]
-fn apropos_search_not_found() -> TestResult {
-    let command = "-k";
-    let bad_page = PAGE_NOT_FOUND;
-    let expected = format!("{}: nothing appropriate", bad_page);  
+fn where_page() -> TestResult {
     AssertCommand::cargo_bin(PRG)?
-        .args([&command, &bad_page])
+        .args(["-w", "man"])
         .assert()
-        .stdout(predicate::str::is_match(expected)?);
+        .success()
+        .stdout(predicate::str::contains("/man1/man.1.gz\n"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--where", "9", "man"])
+        .assert()
+        .code(1)
+        .stdout("No manual entry for man in section 9\n");
 
     Ok(())
 }
 
-// Requires a file with limited permissions in default search directory.
+// Requires chmod.1.gz and chmod.2.gz or alternative pages in default search directory.
 #[test]
-fn index_cache_refresh() -> TestResult {
-    let cmd = INDEX_CMD;
-    let expected = format!("Successfully updated manual entries in database.");
+fn resolve_page_paths_without_side_effects() -> TestResult {
+    // Build the index first if needed, as resolve_page only reads it.
+    let output = AssertCommand::cargo_bin(PRG)?
+        .args(["-a", "-w", "chmod"])
+        .assert()
+        .success();
+    let expected = String::from_utf8(output.get_output().stdout.clone())?;
+
+    let paths = resolve_page("chmod", None)?;
+    let found: String = paths.iter().map(|path| format!("{}\n", path.display())).collect();
+    assert_eq!(found, expected);
+    assert!(paths[0].ends_with("chmod.1.gz"));
+
+    let paths = resolve_page("chmod", Some("2"))?;
+    assert_eq!(paths.len(), 1);
+    assert!(paths[0].ends_with("chmod.2.gz"));
 
+    assert!(resolve_page(PAGE_NOT_FOUND, None)?.is_empty());
+
+    Ok(())
+}
+
+// Requires chmod.2.gz or an alternative page with several sections in default search directory.
+#[test]
+fn where_page_with_inline_section() -> TestResult {
     AssertCommand::cargo_bin(PRG)?
-        .args([&cmd])
+        .args(["-w", "chmod.2"])
         .assert()
-        .stdout(predicate::str::contains(expected));
+        .success()
+        .stdout(predicate::str::ends_with("chmod.2.gz\n"));
+
+    // A name with dots but no section after the last one is looked up whole.
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-w", "chmod.x2"])
+        .assert()
+        .code(1)
+        .stdout("No manual entry for chmod.x2\n");
 
     Ok(())
 }
 
-// Requires permdenied.1.gz or an alternative page with limited permissions in default search directory.
+// Requires groff, so headings are marked as bold text.
 #[test]
-fn page_open_permission_denied() -> TestResult {
-    let bad_page = PERM_DENIED_CMD;
-    let expected = format!("Permission denied for {} in section", bad_page);
+fn run_page_with_color() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env_remove("GROFF_NO_SGR")
+        .args(["--color", "--pager", "cat", "man"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[1;31mNAME"));
+
+    Ok(())
+}
+
+#[test]
+fn run_page_missing_from_section_with_fallback() -> TestResult {
+    let expected = render_page(MAN1_GZ)?;
 
     AssertCommand::cargo_bin(PRG)?
-        .args([&bad_page])
+        .args(["--fallback-section", "--pager", "cat", "9", "man"])
         .assert()
-        .stdout(predicate::str::contains(expected));
+        .success()
+        .stderr(predicate::str::contains("manr: no manual entry for man in section 9, showing section 1"))
+        .stdout(predicate::str::contains(format!("{}", expected)));
 
     Ok(())
 }
 
-// Requires badgzip.1.gz or an alternative page that can't be extracted as gzip in default search directory.
+// Requires table.1.gz, whose first line asks for tbl, in default search directory.
 #[test]
-fn gzip_extract_failed() -> TestResult {
-    let bad_page = BAD_GZ_CMD;
-    let expected = format!("Error extracting gzip file for {} in section", bad_page);
+fn show_command_for_page() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANWIDTH", "100")
+        .args(["--show-command", "--pager", "cat", TABLE_CMD])
+        .assert()
+        .success()
+        .stdout("groff -mandoc -t -Tutf8 -rLL=100n -rLT=100n\n");
 
+    // Widths outside the readable range are clamped.
     AssertCommand::cargo_bin(PRG)?
-        .args([&bad_page])
+        .env("MANWIDTH", "5")
+        .args(["--show-command", "--pager", "cat", TABLE_CMD])
         .assert()
-        .stderr(predicate::str::contains(expected));
+        .success()
+        .stdout("groff -mandoc -t -Tutf8 -rLL=20n -rLT=20n\n");
+
+    Ok(())
+}
+
+// Requires table.1.gz, whose first line asks for tbl, in default search directory.
+#[test]
+fn run_page_with_preprocessor_hint() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--pager", "cat", TABLE_CMD])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alpha"))
+        .stdout(predicate::str::contains("l l.").not());
+
+    Ok(())
+}
+
+// Requires plain.1, an uncompressed page, in default search directory.
+#[test]
+fn run_uncompressed_page() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--pager", "cat", "plain"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("This page is stored without gzip compression."));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-f", "plain"])
+        .assert()
+        .success()
+        .stdout("plain (1) - an uncompressed manual page\n");
+
+    Ok(())
+}
+
+// Requires stub.1.gz, containing only ".so man1/man.1", and man.1.gz in default search directory.
+#[test]
+fn run_page_redirected_with_so() -> TestResult {
+    let expected = render_page(MAN1_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--pager", "cat", "stub"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-f", "stub"])
+        .assert()
+        .success()
+        .stdout("stub (1) - an interface to the system reference manuals\n");
+
+    Ok(())
+}
+
+// Requires bzipped.1.bz2 and xzipped.1.xz in default search directory.
+#[test]
+fn run_bzip2_and_xz_compressed_pages() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--pager", "cat", "bzipped"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("This page is stored with bzip2 compression."));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--pager", "cat", "xzipped"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("This page is stored with xz compression."));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-k", "compressed manual page"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("bzipped (1) - a bzip2 compressed manual page"))
+        .stdout(predicate::str::contains("xzipped (1) - an xz compressed manual page"));
+
+    Ok(())
+}
+
+// Requires latin1.1.gz or an alternative page encoded in ISO-8859-1 in default search directory.
+#[test]
+fn run_legacy_encoded_page_with_encoding_detection() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--encoding-detect", "--pager", "cat", "latin1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("café crème"));
+
+    Ok(())
+}
+
+// Pages in legacy encodings are converted while indexing, so their descriptions are found without errors.
+#[test]
+fn whatis_search_legacy_encoded_page() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-legacy-encoding-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy("./tests/inputs/latin1.1.gz", root.join("man1/latin1.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Error extracting").not());
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", "latin1"])
+        .assert()
+        .success()
+        .stdout("latin1 (1) - a test page encoded in ISO-8859-1\n");
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn render_local_file_from_stdin() -> TestResult {
+    let contents = decompress(MAN1_GZ.to_string(), ErrorAction::Fail)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-l", "-", "--format", "html"])
+        .write_stdin(contents.clone())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("<html"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-l", "-"])
+        .write_stdin(contents)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("NAME"))
+        .stdout(predicate::str::contains("\x1b[").not());
+
+    Ok(())
+}
+
+// Local files are rendered by relative or absolute path, whether compressed or not.
+#[test]
+fn run_local_file_by_path() -> TestResult {
+    let absolute = format!("{}/tests/inputs/xzipped.1.xz", env!("CARGO_MANIFEST_DIR"));
+    for (path, expected) in [("./tests/inputs/plain.1", "an uncompressed manual page"), (absolute.as_str(), "an xz compressed manual page")] {
+        AssertCommand::cargo_bin(PRG)?
+            .args(["--local-file", path])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-l", "./tests/inputs/missing.1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("manr: cannot open ./tests/inputs/missing.1: "));
+
+    Ok(())
+}
+
+// Requires groff, as only formatted output is cached.
+#[test]
+fn run_page_from_render_cache() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-render-cache-{}", std::process::id()));
+    let page = test_dir.join("man1/plain.1");
+    let config = test_dir.join("config.toml");
+    let render_cache = test_dir.join("render_cache");
+    fs::create_dir_all(test_dir.join("man1"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\ncache_rendered_pages = true\n", test_dir.display()))?;
+    fs::copy("./tests/inputs/plain.1", &page)?;
+
+    let run = |expected: &str| -> TestResult {
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &test_dir)
+            .args(["--pager", "cat", "plain"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+        Ok(())
+    };
+
+    run("an uncompressed manual page")?;
+    assert_eq!(fs::read_dir(&render_cache)?.count(), 1);
+
+    // A page changed on disk is formatted again and replaces its old rendering.
+    fs::write(&page, fs::read_to_string(&page)?.replace("an uncompressed", "a changed"))?;
+    fs::File::options().write(true).open(&page)?.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))?;
+    run("a changed manual page")?;
+    assert_eq!(fs::read_dir(&render_cache)?.count(), 1);
+
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Requires MAN1/upcase.1.gz or an alternative page in an uppercase section directory in default search directory.
+#[test]
+fn run_page_with_section_in_uppercase_directory() -> TestResult {
+    let page = UPCASE_CMD;
+    let section = "1";
+    let expected = render_page(UPCASE1_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&section, &page])
+        .assert()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
+    Ok(())
+}
+
+#[test]
+fn run_pages_matching_glob() -> TestResult {
+    let command = "--glob";
+    let pattern = "chm?d";
+    let expected = render_page(CHMOD1_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &pattern])
+        .assert()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
+    Ok(())
+}
+
+// Pages matching a glob are found in the manual directories without the index.
+#[test]
+fn run_pages_matching_glob_without_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-glob-no-index-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+    let expected = render_page(CHMOD1_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", test_dir.join("cache"))
+        .args(["--no-index", "--glob", "chm?d"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected));
+    assert!(!test_dir.join("cache").exists());
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn glob_not_found() -> TestResult {
+    let command = "--glob";
+    let pattern = format!("{}*", PAGE_NOT_FOUND);
+    let expected = format!("No manual entries match {}", pattern);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &pattern.as_str()])
+        .assert()
+        .stdout(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+#[test]
+fn run_page_from_section_list() -> TestResult {
+    let page = "chmod";
+    let sections = "9,2:1";
+    let expected = render_page(CHMOD2_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-s", &sections, &page])
+        .assert()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
+    Ok(())
+}
+
+#[test]
+fn page_not_found_in_section_list() -> TestResult {
+    let page = "man";
+    let sections = "2:3";
+    let expected = format!("No manual entry for {} in section 2,3", page);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--sections", &sections, &page])
+        .assert()
+        .stdout(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+#[test]
+fn page_not_found() -> TestResult {
+    let bad_page = PAGE_NOT_FOUND;
+    let expected = format!("No manual entry for {}", bad_page);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&bad_page])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_match(expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn page_not_found_when_opening_multiple() -> TestResult {
+    let page1 = "man";
+    let expected1 = render_page(MAN1_GZ)?;
+    let page2 = "chmod";
+    let expected2 = render_page(CHMOD1_GZ)?;
+    let page3 = "cat";
+    let expected3 = render_page(CAT1_GZ)?;
+    let bad_page = PAGE_NOT_FOUND;
+    let expected4 = format!("No manual entry for {}", bad_page);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&page1, &page2, &page3, &bad_page])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected1)))
+        .stdout(predicate::str::contains(format!("{}", expected2)))
+        .stdout(predicate::str::contains(format!("{}", expected3)))
+        .stdout(predicate::str::is_match(expected4)?);
+
+    Ok(())
+}
+
+#[test]
+fn page_section_not_found() -> TestResult {
+    let page = "man";   // Same result for a bad page
+    let bad_sect = SECT_NOT_FOUND;
+    let expected = format!("No manual entry for {} in section {}\n", page, bad_sect);
+    AssertCommand::cargo_bin(PRG)?
+        .args([&bad_sect, &page])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_match(expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn page_section_not_found_when_opening_multiple() -> TestResult {
+    let page1 = "man";
+    let section1 = "7";
+    let expected1 = render_page(MAN7_GZ)?;
+    let page2 = "chmod";
+    let section2 = "2";
+    let expected2 = render_page(CHMOD2_GZ)?;
+    let page3 = "chroot";
+    let section3 = "8";
+    let expected3 = render_page(CHROOT8_GZ)?;
+    let page4 = "cat";    
+    let bad_section = SECT_NOT_FOUND;
+    let expected4 = format!("No manual entry for {} in section {}", page4, bad_section);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&section1, &page1, &section2, &page2, &section3, &page3, &bad_section, &page4])
+        .assert()
+        .stdout(predicate::str::contains(format!("{}", expected1)))
+        .stdout(predicate::str::contains(format!("{}", expected2)))
+        .stdout(predicate::str::contains(format!("{}", expected3)))
+        .stdout(predicate::str::is_match(expected4)?);
+
+    Ok(())
+}
+
+#[test]
+fn page_stats() -> TestResult {
+    let command = "--stats";
+    let page = "man";
+    let expected = vec!["Sections:    ", "Tables:      ", "Equations:   no"];
+
+    let mut assert = AssertCommand::cargo_bin(PRG)?
+        .args([&command, &page])
+        .assert();
+    for line in expected {
+        assert = assert.stdout(predicate::str::contains(line));
+    }
+
+    Ok(())
+}
+
+// Pages in an archive are sized by their member, as they have no file of their own.
+#[test]
+fn page_stats_in_archive() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-stats-archive-{}", std::process::id()));
+    let archive = test_dir.join("pages.tar");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(test_dir.join("man"))?;
+    let mut builder = tar::Builder::new(fs::File::create(&archive)?);
+    builder.append_path_with_name(CHMOD1_GZ, "man1/chmod.1.gz")?;
+    builder.finish()?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\narchives = [\"{}\"]\n", test_dir.join("man").display(), archive.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["--stats", "chmod"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("File:        {}!/man1/chmod.1.gz\n", archive.display())))
+        .stdout(predicate::str::contains(format!(" ({} compressed)\n", fs::metadata(CHMOD1_GZ)?.len())));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn list_section_names_only() -> TestResult {
+    let command = "--list";
+    let section = "7";
+    let expected = "(?m)^man$";
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &section, "--names-only"])
+        .assert()
+        .stdout(predicate::str::is_match(expected)?)
+        .stdout(predicate::str::contains("(7)").not());
+
+    Ok(())
+}
+
+#[test]
+fn trailing_section_without_page() -> TestResult {
+    let page = "man";
+    let section = "3";
+    let expected1 = render_page(MAN1_GZ)?;
+    let expected2 = format!("What manual page do you want from section {}?", section);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&page, &section])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected1)))
+        .stdout(predicate::str::contains(expected2.clone()));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&page, "cat", &section])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected2));
+
+    Ok(())
+}
+
+#[test]
+fn page_toc() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--toc", "man"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^NAME$")?)
+        .stdout(predicate::str::is_match(r"(?m)^DESCRIPTION$")?)
+        .stdout(predicate::str::contains(".SH").not());
+
+    Ok(())
+}
+
+#[test]
+fn index_stats() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--stats-index"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^Entries: +[1-9][0-9]*$")?)
+        .stdout(predicate::str::contains("Empty descriptions:"))
+        .stdout(predicate::str::is_match(r"(?m)^  1 +[1-9][0-9]*$")?);
+
+    Ok(())
+}
+
+#[test]
+fn explain_equivalent_man_command() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--explain", "--sections", "2,3", "--pager", "less -R", "printf"])
+        .assert()
+        .success()
+        .stdout("man -P 'less -R' -S 2:3 printf\n");
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--explain", "03", "printf"])
+        .assert()
+        .success()
+        .stdout("man 3 printf\n");
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--explain", "--list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has no equivalent man command"));
+
+    Ok(())
+}
+
+#[test]
+fn json_page() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--json-page", "cat"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""name":"cat","section":"1""#))
+        .stdout(predicate::str::contains(r#""description":"concatenate files and print on the standard output""#))
+        .stdout(predicate::str::contains(r#""body":""#));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--json-page", "cat", "--no-body"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""name":"cat""#))
+        .stdout(predicate::str::contains(r#""body""#).not());
+
+    Ok(())
+}
+
+#[test]
+fn changed_since() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--changed-since", "0d"])
+        .assert()
+        .success()
+        .stdout("No manual pages changed since 0d\n");
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--changed-since", "yesterday"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("manr: invalid time 'yesterday', expected 'last' or a duration such as 7d"));
+
+    Ok(())
+}
+
+// Pages modified since they were indexed are listed as changed, and pages added since as new.
+#[test]
+fn changed_since_last_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-changed-since-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::copy(CAT1_GZ, root.join("man1/cat.1.gz"))?;
+    // A symlink giving a page another name is indexed under the page it links to, rather than reported as new.
+    std::os::unix::fs::symlink("chmod.1.gz", root.join("man1/chmodlink.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["--changed-since", "last"])
+        .assert()
+        .success()
+        .stdout("No manual pages changed since last\n");
+
+    // Modification times are saved in whole seconds.
+    let touched = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+    fs::File::options().append(true).open(root.join("man1/chmod.1.gz"))?.set_modified(touched)?;
+    fs::create_dir_all(root.join("man2"))?;
+    fs::copy(CHMOD2_GZ, root.join("man2/chmod.2.gz"))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["--changed-since", "last"])
+        .assert()
+        .success()
+        .stdout("chmod (1) - changed\nchmod (2) - new\n");
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn usage_and_help() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .arg("--usage")
+        .assert()
+        .success()
+        .stdout("Usage: manr [OPTION...] [SECTION] PAGE...\n");
+
+    AssertCommand::cargo_bin(PRG)?
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("Usage: manr [OPTION...] [SECTION] PAGE...\n"))
+        .stdout(predicate::str::contains("-k, --apropos TERM"))
+        .stdout(predicate::str::contains("manr 3 printf"))
+        .stdout(predicate::str::contains("unrecognised option").not());
+
+    Ok(())
+}
+
+#[test]
+fn section_info() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--section-info", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Section 3: Library functions, especially the C standard library"))
+        .stdout(predicate::str::contains("Indexed pages: "));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--section-info", "12"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No section 12"));
+
+    Ok(())
+}
+
+// A descriptions.toml next to the config file replaces the description extracted from a page.
+#[test]
+fn whatis_search_uses_description_override() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-description-override-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy("./tests/inputs/table.1.gz", root.join("man1/table.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+    fs::write(test_dir.join("descriptions.toml"), "\"table.1\" = \"a table with an overridden description\"\n")?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", TABLE_CMD])
+        .assert()
+        .success()
+        .stdout("table (1) - a table with an overridden description\n");
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn run_page_with_padded_section() -> TestResult {
+    let page = "man";
+    let expected = render_page(MAN7_GZ)?;
+
+    for section in ["07", " 7", "7 "] {
+        AssertCommand::cargo_bin(PRG)?
+            .args([section, page])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!("{}", expected)));
+
+        AssertCommand::cargo_bin(PRG)?
+            .args(["cat", section, page])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!("{}", expected)));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn apropos_search_with_section_breakdown() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--breakdown", "-k", "chmod"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^Sections: .*1: [1-9][0-9]*, 2: [1-9][0-9]*")?);
+
+    Ok(())
+}
+
+// Requires versioned-1.2.1.gz or an alternative page with a version in its filename in default search directory.
+#[test]
+fn run_page_stored_with_version_suffix() -> TestResult {
+    let page = VERSIONED_CMD;
+    let expected = render_page(VERSIONED1_GZ)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&page])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}", expected)));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-f", &page])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("versioned (1) - a test page installed with a version in its filename"));
+
+    Ok(())
+}
+
+// Requires tests/extra/man1/projectpage.1.gz, which is outside the default search directory.
+#[test]
+fn whatis_search_in_extra_path() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-f", "projectpage"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1) - a test page found only through MANR_EXTRA_PATH"));
+
+    Ok(())
+}
+
+#[test]
+fn whatis_and_apropos_search_ignore_case() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-f", "ProjectPage"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-k", "FOUND ONLY"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)"));
+
+    Ok(())
+}
+
+#[test]
+fn apropos_search_with_regex() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["--regex", "-k", "^project.*e$"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--regex", "-k", "(unclosed"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("manr: invalid regular expression '(unclosed'"));
+
+    Ok(())
+}
+
+#[test]
+fn apropos_search_in_section() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-k", "-s", "1", "projectpage"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-k", "-s", "3", "projectpage"])
+        .assert()
+        .success()
+        .stdout("projectpage: nothing appropriate\n");
+
+    Ok(())
+}
+
+#[test]
+fn global_apropos_search_in_page_text() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-K", "Outside the DEFAULT search directory"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)\n"));
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--max-results", "1", "-K", "name"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^[^\n]+ \([^)]+\)\n$")?);
+
+    // Troff requests aren't part of the page text.
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_EXTRA_PATH", "./tests/extra")
+        .args(["-K", ".SH NAME"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("projectpage (1)").not());
+
+    Ok(())
+}
+
+// Requires escapes.1.gz or an alternative page with troff escapes in its NAME section in default search directory.
+#[test]
+fn whatis_search_unescapes_description() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args([INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-f", "escapes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("escapes (1) - print bold text with 'quotes' and 'strings' spaced"));
+
+    Ok(())
+}
+
+#[test]
+fn whatis_search() -> TestResult {
+    let command = "-f";
+    let page = "man";
+    let expected = vec!["man (1) - an interface to the system reference manuals", "man (7) - macros to format man pages"];  
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &page])
+        .assert()
+        .stdout(predicate::str::contains(expected.join("\n")));
+  
+    Ok(())
+}
+
+#[test]
+fn whatis_search_json() -> TestResult {
+    let expected = r#"[{"page":"man","section":"1","description":"an interface to the system reference manuals"},{"page":"man","section":"7","description":"macros to format man pages"}]"#;
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--json", "-f", "man"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", expected));
+
+    // Nothing found is still valid JSON.
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-k", PAGE_NOT_FOUND, "--json"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+
+    Ok(())
+}
+
+#[test]
+fn whatis_search_not_found() -> TestResult {
+    let command = "-f";
+    let bad_page = PAGE_NOT_FOUND;
+    let expected = format!("{}: nothing appropriate", bad_page);  
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &bad_page])
+        .assert()
+        .stdout(predicate::str::is_match(expected)?);
+
+    Ok(())
+}
+
+#[test]
+fn apropos_search() -> TestResult {
+    let command = "-k";
+    let page = "zcat";
+    let expected = vec!("bzcat (1) - a block-sorting file compressor, v1.0.8",
+    "lzcat (1) - Compress or decompress .xz and .lzma files",
+    "xzcat (1) - Compress or decompress .xz and .lzma files",
+    "zcat (1) - compress or expand files");
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &page])
+        .assert()
+        .stdout(predicate::str::contains(expected.join("\n")));
+
+    Ok(())
+}
+
+#[test]
+fn apropos_search_not_found() -> TestResult {
+    let command = "-k";
+    let bad_page = PAGE_NOT_FOUND;
+    let expected = format!("{}: nothing appropriate", bad_page);  
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, &bad_page])
+        .assert()
+        .stdout(predicate::str::is_match(expected)?);
+
+    Ok(())
+}
+
+// A page whose gzip header stores a different name is indexed under its installed name, unless use_gzip_header_names is set.
+#[test]
+fn whatis_search_uses_gzip_header_name() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-header-name-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy("./tests/inputs/mangled.1.gz", root.join("man1/mangled.1.gz"))?;
+
+    for (setting, page) in [("false", "mangled"), ("true", "renamed")] {
+        fs::write(&config, format!("[default]\nfile_path = \"{}\"\nuse_gzip_header_names = {}\n", root.display(), setting))?;
+
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &test_dir)
+            .args(["-q", INDEX_CMD])
+            .assert()
+            .success();
+
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &test_dir)
+            .args(["-f", page])
+            .assert()
+            .success()
+            .stdout(format!("{} (1) - a test page whose compressed file was renamed after packaging\n", page));
+    }
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// A gzip header name without the section suffix of the installed file keeps the file's full section.
+#[test]
+fn whatis_search_keeps_section_suffix_with_gzip_header_name() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-section-suffix-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy("./tests/inputs/mangled.1.gz", root.join("man1/mangled.1ssl.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\nuse_gzip_header_names = true\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", "renamed"])
+        .assert()
+        .success()
+        .stdout("renamed (1ssl) - a test page whose compressed file was renamed after packaging\n");
+
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Requires multiname.1.gz or an alternative page listing several names in its NAME section in default search directory.
+#[test]
+fn whatis_search_for_each_name_of_page() -> TestResult {
+    for page in ["multiname", "othername", "thirdname"] {
+        AssertCommand::cargo_bin(PRG)?
+            .args(["-f", page])
+            .assert()
+            .success()
+            .stdout(format!("{} (1) - a test page documenting several commands\n", page));
+    }
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-w", "othername"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("/multiname.1.gz\n"));
+
+    Ok(())
+}
+
+// Requires mdocpage.1.gz or an alternative page using BSD mdoc macros in its NAME section in default search directory.
+#[test]
+fn whatis_search_mdoc_page() -> TestResult {
+    for page in ["mdocpage", "mdoc-alias"] {
+        AssertCommand::cargo_bin(PRG)?
+            .args(["-f", page])
+            .assert()
+            .success()
+            .stdout(format!("{} (1) - a test page written with BSD mdoc macros\n", page));
+    }
+
+    Ok(())
+}
+
+// Requires a file with limited permissions in default search directory.
+#[test]
+fn index_cache_refresh() -> TestResult {
+    let cmd = INDEX_CMD;
+    let expected = format!("Successfully updated manual entries in database.");
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&cmd])
+        .assert()
+        .stdout(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// Requires permdenied.1.gz or an alternative page with limited permissions in default search directory.
+#[test]
+fn page_open_permission_denied() -> TestResult {
+    let bad_page = PERM_DENIED_CMD;
+    let expected = format!("Permission denied for {} in section", bad_page);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&bad_page])
+        .assert()
+        .stdout(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// Requires badgzip.1.gz or an alternative page that can't be extracted as gzip in default search directory.
+#[test]
+fn gzip_extract_failed() -> TestResult {
+    let bad_page = BAD_GZ_CMD;
+    let expected = format!("Error extracting gzip file for {} in section", bad_page);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&bad_page])
+        .assert()
+        .stderr(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// Failing to extract a page through the library returns an error, rather than exiting the test process.
+#[test]
+fn decompress_fail_returns_reported_error() -> TestResult {
+    let missing = decompress(format!("./tests/inputs/{}.1.gz", PAGE_NOT_FOUND), ErrorAction::Fail);
+    assert!(missing.is_err_and(|e| e.is::<ReportedError>()));
+
+    let bad = decompress(BAD_GZ.to_string(), ErrorAction::Fail);
+    assert!(bad.is_err_and(|e| e.is::<ReportedError>()));
+
+    Ok(())
+}
+
+// Requires groff and less to be missing, so the PATH is cleared and the page source is printed with macros stripped.
+// The pager isn't needed as stdout isn't a terminal.
+#[test]
+fn run_without_groff_or_less_prints_stripped_source() -> TestResult {
+    let page = "cat";
+    let expected = "concatenate files and print on the standard output";
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("PATH", "")
+        .args([&page])
+        .assert()
+        .stdout(predicate::str::contains(expected))
+        .stderr(predicate::str::contains("groff not found (install groff or set MANR_FORMATTER)"))
+        .stderr(predicate::str::contains("less not found").not());
+
+    Ok(())
+}
+
+// Requires links.1.gz or an alternative page with .UR and .MT links in default search directory.
+#[test]
+fn run_without_groff_shows_link_addresses() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .env("PATH", "")
+        .args(["--hyperlinks", "links"])
+        .assert()
+        .stdout(predicate::str::contains("project website <https://example.org/manr>."))
+        .stdout(predicate::str::contains("Report bugs to <bugs@example.org>."));
+
+    Ok(())
+}
+
+#[test]
+fn all_pages_returns_sorted_index_entries() -> TestResult {
+    // Run any lookup first so the index cache is created if it doesn't exist yet.
+    AssertCommand::cargo_bin(PRG)?
+        .args([PAGE_NOT_FOUND])
+        .assert();
+
+    let pages = all_pages()?;
+    let names: Vec<(&str, &str)> = pages.iter().map(|cache| (cache.page.as_str(), cache.section.as_str())).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+
+    assert_eq!(names, sorted);
+    assert!(pages.iter().any(|cache| cache.page == "man" && cache.section == "7"));
+
+    Ok(())
+}
+
+#[test]
+fn update_index_for_one_section() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args([INDEX_CMD, "7"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"Successfully updated [1-9][0-9]* manual entries in section 7")?);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["-f", "man"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("man (1)"))
+        .stdout(predicate::str::contains("man (7)"));
+
+    Ok(())
+}
+
+#[test]
+fn index_ids_follow_sorted_entries() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args([INDEX_CMD])
+        .assert()
+        .success();
+
+    let ids: Vec<u32> = all_pages()?.iter().map(|cache| cache.id).collect();
+    let expected: Vec<u32> = (1..=ids.len() as u32).collect();
+
+    assert_eq!(ids, expected);
+
+    Ok(())
+}
+
+// Indexing the test pages in parallel gives the same index as indexing them one at a time.
+#[test]
+fn parallel_index_matches_serial_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-parallel-{}", std::process::id()));
+    let inputs = format!("{}/tests/inputs", env!("CARGO_MANIFEST_DIR"));
+    let mut indexes = Vec::new();
+
+    for parallel in [false, true] {
+        let cache_dir = test_dir.join(if parallel { "parallel" } else { "serial" });
+        let config = test_dir.join(format!("{}.toml", parallel));
+        fs::create_dir_all(&cache_dir)?;
+        fs::write(&config, format!("[default]\nfile_path = \"{}\"\nparallel_indexing = {}\n", inputs, parallel))?;
+
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &cache_dir)
+            .args(["-q", INDEX_CMD])
+            .assert()
+            .success();
+
+        indexes.push(fs::read(cache_dir.join("index.bin"))?);
+    }
+    fs::remove_dir_all(&test_dir)?;
+
+    assert_eq!(indexes[0], indexes[1]);
+
+    Ok(())
+}
+
+// Sections of a page are listed in the order set by $MANSECT or the section_order config key, with unlisted sections after them.
+#[test]
+fn where_page_in_configured_section_order() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-section-order-{}", std::process::id()));
+    let inputs = format!("{}/tests/inputs", env!("CARGO_MANIFEST_DIR"));
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(&test_dir)?;
+
+    for (order, expected) in [("[]", ["chmod.1.gz", "chmod.2.gz"]), ("[\"8\", 2]", ["chmod.2.gz", "chmod.1.gz"])] {
+        fs::write(&config, format!("[default]\nfile_path = \"{}\"\nsection_order = {}\n", inputs, order))?;
+
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &test_dir)
+            .args(["-q", INDEX_CMD])
+            .assert()
+            .success();
+
+        let output = AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", &test_dir)
+            .args(["-a", "-w", "chmod"])
+            .assert()
+            .success();
+        let paths: Vec<String> = String::from_utf8(output.get_output().stdout.clone())?.lines().map(|line| line.to_string()).collect();
+
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with(expected[0]) && paths[1].ends_with(expected[1]));
+    }
+
+    // $MANSECT takes precedence over the config key.
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .env("MANSECT", "1:8")
+        .args(["-w", "chmod"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("chmod.1.gz\n").and(predicate::str::ends_with("chmod.2.gz\n")));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Completion scripts are printed for each supported shell, and complete page names from the index without building it.
+#[test]
+fn completions_for_shells_and_page_names() -> TestResult {
+    for (shell, expected) in [("bash", "complete -F _manr manr"), ("zsh", "compdef _manr manr"), ("fish", "complete -c manr -l whatis -s f")] {
+        AssertCommand::cargo_bin(PRG)?
+            .args(["--completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected).and(predicate::str::contains("--completion-pages")));
+    }
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--completions", "tcsh"])
+        .assert()
+        .failure()
+        .stderr("manr: unsupported shell 'tcsh', expected bash, zsh or fish\n");
+
+    let test_dir = env::temp_dir().join(format!("manr-completions-{}", std::process::id()));
+    let inputs = format!("{}/tests/inputs", env!("CARGO_MANIFEST_DIR"));
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(&test_dir)?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", inputs))?;
+
+    // Without an index nothing is completed, and none is built.
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .arg("--completion-pages")
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!test_dir.join("index.bin").exists());
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    // Pages in several sections are listed once.
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .arg("--completion-pages")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\nchmod\nchroot\n"));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Pages are found by searching the manual directories when the index isn't used or can't be built.
+#[test]
+fn where_page_without_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-no-index-{}", std::process::id()));
+    let inputs = format!("{}/tests/inputs", env!("CARGO_MANIFEST_DIR"));
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(&test_dir)?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", inputs))?;
+    let expected = format!("{}/chmod.1.gz\n{}/chmod.2.gz\n", inputs, inputs);
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", test_dir.join("cache"))
+        .args(["--no-index", "-w", "chmod"])
+        .assert()
+        .success()
+        .stdout(expected.clone());
+    assert!(!test_dir.join("cache").exists());
+
+    // The cache directory can't be created inside a file.
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", config.join("cache"))
+        .args(["-q", "-w", "chmod"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("couldn't build the index"))
+        .stdout(expected);
+
+    // Page names are matched ignoring case, as they are in the index.
+    let root = test_dir.join("man");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/Chmod.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", test_dir.join("cache"))
+        .args(["--no-index", "-w", "chmod"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", root.join("man1/Chmod.1.gz").display()));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// An empty entry in $MANPATH is replaced by the configured directories.
+#[test]
+fn where_page_with_empty_manpath_entry() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-manpath-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let other = test_dir.join("other");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::create_dir_all(other.join("man2"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::copy(CHMOD2_GZ, other.join("man2/chmod.2.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    for manpath in [format!("{}:", other.display()), format!(":{}", other.display()), format!("{}::", other.display())] {
+        AssertCommand::cargo_bin(PRG)?
+            .env("MANR_CONFIG", &config)
+            .env("MANR_CACHE", test_dir.join("cache"))
+            .env("MANPATH", manpath)
+            .args(["--no-index", "-w", "chmod"])
+            .assert()
+            .success()
+            .stdout(format!("{}\n{}\n", root.join("man1/chmod.1.gz").display(), other.join("man2/chmod.2.gz").display()));
+    }
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// A page reached through a symlinked directory or a symlink with another name is only indexed once, under both names.
+#[test]
+fn index_symlinked_page_once() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-symlinks-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    std::os::unix::fs::symlink(root.join("man1"), root.join("mirror"))?;
+    std::os::unix::fs::symlink("chmod.1.gz", root.join("man1/chmodlink.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-a", "-w", "chmod"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", root.join("man1/chmod.1.gz").display()));
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", "chmodlink"])
+        .assert()
+        .success()
+        .stdout("chmodlink (1) - change file mode bits\n");
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-w", "chmodlink"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", root.join("man1/chmod.1.gz").display()));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Pages added after the index was built are found once the stale index is updated, or only warned about when stale_index is "warn".
+#[test]
+fn update_stale_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-stale-index-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\nstale_index = \"warn\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    // Directory modification times are compared in whole seconds.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::create_dir_all(root.join("man2"))?;
+    fs::copy(CHMOD2_GZ, root.join("man2/chmod.2.gz"))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-a", "-w", "chmod"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("manr: warning: the manual directories have changed since the index was built"))
+        .stdout(format!("{}\n", root.join("man1/chmod.1.gz").display()));
+
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-a", "-w", "chmod"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("manr: the manual directories have changed since the index was built; updating the index..."))
+        .stdout(format!("{}\n{}\n", root.join("man1/chmod.1.gz").display(), root.join("man2/chmod.2.gz").display()));
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["--update-if-stale"])
+        .assert()
+        .success()
+        .stdout("The index is up to date.\n");
+
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// The index is built on first run without a message or prompt when the user isn't at a terminal.
+#[test]
+fn build_index_on_first_run_without_terminal() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-first-run-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", "chmod"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("No index found").not())
+        .stdout("chmod (1) - change file mode bits\n");
+    assert!(test_dir.join("index.bin").exists());
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// An index file that can't be read is rebuilt without adding to the output of the search that found it.
+#[test]
+fn rebuild_unreadable_index() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-unreadable-index-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    fs::write(test_dir.join("index.bin"), "not an index")?;
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-f", "chmod"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("rebuilding it"))
+        .stdout("chmod (1) - change file mode bits\n");
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+// Root isn't restricted by directory permissions, so the test is skipped with a message when run as root.
+#[test]
+fn index_skips_unreadable_directory() -> TestResult {
+    let status = fs::read_to_string("/proc/self/status")?;
+    let effective_uid = status.lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().nth(1))
+        .ok_or("no effective uid in /proc/self/status")?;
+    if effective_uid == "0" {
+        eprintln!("skipping index_skips_unreadable_directory: directory permissions don't apply to root");
+        return Ok(());
+    }
+
+    let test_dir = env::temp_dir().join(format!("manr-unreadable-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::create_dir_all(root.join("man2"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::copy(CHMOD2_GZ, root.join("man2/chmod.2.gz"))?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+    fs::set_permissions(root.join("man2"), std::os::unix::fs::PermissionsExt::from_mode(0o000))?;
+    let unreadable = fs::read_dir(root.join("man2")).is_err();
+
+    let indexed = AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .output()?;
+    let found = AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-a", "-w", "chmod"])
+        .output()?;
+
+    fs::set_permissions(root.join("man2"), std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
+    fs::remove_dir_all(&test_dir)?;
+
+    assert!(unreadable, "the permission change didn't make the directory unreadable");
+    assert!(indexed.status.success());
+    assert!(String::from_utf8(indexed.stderr)?.contains(&format!("Skipping {}, which can't be read: ", root.join("man2").display())));
+    assert!(found.status.success());
+    assert_eq!(String::from_utf8(found.stdout)?, format!("{}\n", root.join("man1/chmod.1.gz").display()));
+
+    Ok(())
+}
+
+// Browsing needs a terminal, so it fails with an error rather than drawing into a pipe.
+#[test]
+fn browse_without_terminal() -> TestResult {
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--browse"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::ends_with("manr: --browse needs a terminal\n"));
+
+    Ok(())
+}
+
+// Empty page files are left out of the index, but are still reported when opened.
+#[test]
+fn empty_page_file() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-empty-page-{}", std::process::id()));
+    let root = test_dir.join("man");
+    let config = test_dir.join("config.toml");
+    fs::create_dir_all(root.join("man1"))?;
+    fs::copy(CHMOD1_GZ, root.join("man1/chmod.1.gz"))?;
+    fs::write(root.join("man1/empty.1.gz"), "")?;
+    fs::write(&config, format!("[default]\nfile_path = \"{}\"\n", root.display()))?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["-q", INDEX_CMD])
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin(PRG)?
+        .env("MANR_CONFIG", &config)
+        .env("MANR_CACHE", &test_dir)
+        .args(["1", EMPTY_CMD])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("Empty page file for {} in section 1", EMPTY_CMD)))
+        .stderr(predicate::str::contains("No manual entry").not());
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn compare_identical_pages() -> TestResult {
+    let command = "--compare";
+    let expected = format!("Pages {} and {} are identical", CAT1_GZ, CAT1_GZ);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, CAT1_GZ, CAT1_GZ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// Files named without a section, like any path a user can give, are compared rather than rejected.
+#[test]
+fn compare_pages_without_section_in_name() -> TestResult {
+    let test_dir = env::temp_dir().join(format!("manr-compare-{}", std::process::id()));
+    fs::create_dir_all(&test_dir)?;
+    let page = test_dir.join("catpage");
+    fs::copy(CAT1_GZ, &page)?;
+
+    AssertCommand::cargo_bin(PRG)?
+        .args(["--compare", page.to_str().unwrap(), page.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Pages {} and {} are identical", page.display(), page.display())));
+    fs::remove_dir_all(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn compare_different_pages() -> TestResult {
+    let command = "--compare";
+    let expected = format!("--- {}\n+++ {}", CHMOD1_GZ, CHMOD2_GZ);
+
+    AssertCommand::cargo_bin(PRG)?
+        .args([&command, CHMOD1_GZ, CHMOD2_GZ])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(expected));
 
     Ok(())
 }
 
 // (Need to implement:)
-// (Tests for get descriptions from various .nd and "-" formatting)
-// (Test for failed section without page)
+// (Tests for get descriptions from various "-" formatting)